@@ -0,0 +1,170 @@
+//! Runs the programs built from `benches/` over and over, timing every run.
+//!
+//! The timings of each benchmark are kept in `target/bench/`, so the next run can tell
+//! whether the benchmark got slower or faster since.
+
+use std::fs::{self, DirBuilder};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use build::Program;
+use test::{self, Outcome};
+use utils;
+use ansi_term::Color::{Green, Red};
+
+/// Changes smaller than this, in percent of the previous median, are always noise. Those
+/// within the standard deviation of the runs are noise too.
+const NOISE_THRESHOLD: f64 = 2.0;
+
+/// The timings of every run of a benchmark, in seconds.
+#[derive(Serialize, Deserialize)]
+struct Summary {
+    runs: usize,
+    mean: f64,
+    median: f64,
+    stddev: f64,
+}
+
+impl Summary {
+    fn new(times: &[f64]) -> Summary {
+        let mut sorted = times.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let runs = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / runs as f64;
+        let median = if runs % 2 == 0 {
+            (sorted[runs / 2 - 1] + sorted[runs / 2]) / 2.0
+        } else {
+            sorted[runs / 2]
+        };
+        // The sample standard deviation, as the runs are a sample of every possible run
+        let stddev = if runs > 1 {
+            let variance = sorted.iter().map(|time| (time - mean).powi(2)).sum::<f64>()
+                / (runs - 1) as f64;
+            variance.sqrt()
+        } else {
+            0.0
+        };
+
+        Summary {
+            runs: runs,
+            mean: mean,
+            median: median,
+            stddev: stddev,
+        }
+    }
+
+    /// Reads the summary a previous run saved, if there is one.
+    fn load(path: &Path) -> Option<Summary> {
+        let contents = fs::read_to_string(path).ok()?;
+        ::toml::from_str(&contents).ok()
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut dir_builder = DirBuilder::new();
+        dir_builder.recursive(true);
+        dir_builder.create(path.parent().unwrap())?;
+        utils::write_if_changed(path, &::toml::to_string(self).unwrap())
+    }
+}
+
+/// Writes a duration in seconds with the unit that suits it best, like `12.34 ms`.
+fn format_time(seconds: f64) -> String {
+    if seconds >= 1.0 {
+        format!("{:.3} s", seconds)
+    } else if seconds >= 1e-3 {
+        format!("{:.3} ms", seconds * 1e3)
+    } else if seconds >= 1e-6 {
+        format!("{:.3} µs", seconds * 1e6)
+    } else {
+        format!("{:.0} ns", seconds * 1e9)
+    }
+}
+
+/// Where the summary of a benchmark is kept between runs.
+fn summary_path(directory: &Path, program: &Program) -> PathBuf {
+    directory
+        .join(&program.package)
+        .join(format!("{}.toml", program.name))
+}
+
+/// Runs every benchmark whose name contains `filter` `runs` times, one after the other so
+/// they do not slow each other down. The summaries are compared against, and then replace,
+/// those in `directory`. Returns false if any benchmark failed.
+pub fn run(programs: &[Program], filter: Option<&str>, runs: usize, directory: &Path) -> bool {
+    let mut success = true;
+
+    for program in programs {
+        let name = format!("{}/{}", program.package, program.name);
+        if let Some(filter) = filter {
+            if !name.contains(filter) {
+                continue;
+            }
+        }
+
+        print!("bench {} ... ", name);
+        io::stdout().flush().unwrap();
+
+        // The first run only warms up the caches, and is not timed
+        let mut times = Vec::<f64>::new();
+        let mut failure = None;
+        for run in 0..runs + 1 {
+            match test::run_program(program, &[], None) {
+                Ok((Outcome::Exited(ref status), _, elapsed)) if status.success() => if run > 0 {
+                    times.push(elapsed.as_secs_f64());
+                },
+                Ok((Outcome::Exited(status), output, _)) => {
+                    let mut output = output;
+                    output.extend(format!("{}\n", status).into_bytes());
+                    failure = Some(output);
+                    break;
+                }
+                Ok((Outcome::TimedOut, _, _)) => unreachable!(),
+                Err(e) => {
+                    let message = format!("Failed to execute `{}`: {}\n", program.path.display(), e);
+                    failure = Some(message.into_bytes());
+                    break;
+                }
+            }
+        }
+
+        if let Some(output) = failure {
+            println!("{}", Red.paint("FAILED"));
+            println!("---- {} output ----", name);
+            io::stdout().write_all(&output).unwrap();
+            success = false;
+            continue;
+        }
+
+        let summary = Summary::new(&times);
+        print!(
+            "mean {}, median {}, stddev {} ({} runs)",
+            format_time(summary.mean),
+            format_time(summary.median),
+            format_time(summary.stddev),
+            summary.runs
+        );
+
+        let path = summary_path(directory, program);
+        match Summary::load(&path) {
+            Some(ref previous) if previous.median > 0.0 => {
+                let change = (summary.median - previous.median) / previous.median * 100.0;
+                let noise = (summary.stddev.max(previous.stddev) / previous.median * 100.0)
+                    .max(NOISE_THRESHOLD);
+                if change >= noise {
+                    println!(", {}", Red.paint(format!("{:+.1}% regressed", change)));
+                } else if change <= -noise {
+                    println!(", {}", Green.paint(format!("{:+.1}% improved", change)));
+                } else {
+                    println!(", no change ({:+.1}%)", change);
+                }
+            }
+            _ => println!(),
+        }
+
+        if let Err(e) = summary.save(&path) {
+            eprintln!("Failed to save `{}`: {}", path.display(), e);
+        }
+    }
+
+    success
+}
@@ -0,0 +1,81 @@
+//! Assembles `.asm` sources with a NASM compatible assembler.
+//!
+//! GNU assembly (`.s` and `.S`) goes through the compiler driver like C does, but
+//! `.asm` files are written for a standalone assembler, which is the same no matter
+//! which compiler the rest of the project is built with.
+
+use std::env;
+use std::path::Path;
+use command::CommandLine;
+use project::Project;
+use super::{cache, CompilerOptions, Os};
+
+/// The object format the assembler produces for the platform we are building for.
+fn default_format(compiler_options: &CompilerOptions) -> &'static str {
+    let arch = match compiler_options.triple {
+        Some(ref triple) => triple.arch.clone(),
+        None => env::consts::ARCH.to_owned(),
+    };
+    let bits32 = match arch.as_str() {
+        "x86" | "i386" | "i486" | "i586" | "i686" => true,
+        _ => false,
+    };
+
+    match (compiler_options.os(), bits32) {
+        (Os::Windows, false) => "win64",
+        (Os::Windows, true) => "win32",
+        (Os::MacOS, false) => "macho64",
+        (Os::MacOS, true) => "macho32",
+        (Os::Unix, false) => "elf64",
+        (Os::Unix, true) => "elf32",
+    }
+}
+
+/// Generates a command assembling a single `.asm` source into the given object file.
+pub fn generate_command(
+    project: &Project,
+    compiler_options: &CompilerOptions,
+    source: &Path,
+    object: &Path,
+) -> CommandLine {
+    let (assembler, format) = match project.build {
+        Some(ref build) => (build.assembler.clone(), build.assembler_format.clone()),
+        None => (None, None),
+    };
+
+    let mut command = CommandLine::configured(&assembler.unwrap_or_else(|| String::from("nasm")));
+    command
+        .arg("-f")
+        .arg(format.unwrap_or_else(|| String::from(default_format(compiler_options))))
+        .path(source)
+        .arg("-o")
+        .path(object);
+
+    // Have the assembler list the files it included, so we know when to assemble again
+    command.arg("-MD").path(&cache::depfile_path(object));
+
+    let profile = &compiler_options.profile;
+    if profile.debug {
+        command.arg("-g");
+    }
+    for define in &profile.defines {
+        command.arg(format!("-D{}", define));
+    }
+
+    // NASM only takes include directories ending with a separator
+    for directory in &compiler_options.dependencies.header_search_directories {
+        let mut directory = directory.display().to_string();
+        if !directory.ends_with('/') && !directory.ends_with('\\') {
+            directory.push('/');
+        }
+        command.arg("-I").arg(directory);
+    }
+
+    if let Some(ref build) = project.build {
+        if let Some(ref options) = build.assembler_options {
+            command.args(options);
+        }
+    }
+
+    command
+}
@@ -0,0 +1,299 @@
+//! Decides which object files in `target/<profile>/obj/` are out of date.
+//!
+//! Every object file, like `main.c.o`, has two small companions sitting beside it:
+//! * `main.c.d`, the dependency file the compiler writes while compiling, listing the
+//!   source and every header it included. GCC and Clang write a Make-style rule, while
+//!   MSVC writes JSON (`/sourceDependencies`).
+//! * `main.c.o.cmd`, the exact command that produced the object, so that changing
+//!   flags, defines or search directories in `Maid.toml` triggers a rebuild.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use command::CommandLine;
+
+/// The path of the dependency file the compiler writes for `object`.
+pub fn depfile_path(object: &Path) -> PathBuf {
+    object.with_extension("d")
+}
+
+/// The path of the file recording the command that produced `output`.
+pub fn command_path(output: &Path) -> PathBuf {
+    // Appended rather than replaced, so `libfoo.a` and `libfoo.so` do not share one
+    let mut path = output.as_os_str().to_owned();
+    path.push(".cmd");
+    PathBuf::from(path)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reads a dependency file and returns every prerequisite listed in it.
+pub fn parse_depfile(depfile: &Path) -> Option<Vec<PathBuf>> {
+    let mut contents = String::new();
+    File::open(depfile).ok()?.read_to_string(&mut contents).ok()?;
+
+    if contents.trim_start().starts_with('{') {
+        parse_source_dependencies(&contents)
+    } else {
+        parse_make_rule(&contents)
+    }
+}
+
+/// Reads a Make-style rule (`object.o: source.c header.h \`).
+fn parse_make_rule(contents: &str) -> Option<Vec<PathBuf>> {
+    // Everything before the first unescaped ": " is the target, which we already know.
+    let rule = match contents.find(": ") {
+        Some(index) => &contents[index + 2..],
+        None => return None,
+    };
+
+    let mut prerequisites = Vec::<PathBuf>::new();
+    let mut current = String::new();
+    let mut chars = rule.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // `\ ` is an escaped space inside a path, and `\` at the end of a line continues the rule.
+            '\\' => match chars.peek() {
+                Some(&' ') => {
+                    current.push(' ');
+                    chars.next();
+                }
+                Some(&'\n') | Some(&'\r') => {}
+                _ => current.push(c),
+            },
+            ' ' | '\t' | '\n' | '\r' => if !current.is_empty() {
+                prerequisites.push(PathBuf::from(current.clone()));
+                current.clear();
+            },
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        prerequisites.push(PathBuf::from(current));
+    }
+
+    Some(prerequisites)
+}
+
+/// Reads the JSON written by MSVC's `/sourceDependencies`, which looks like
+/// `{ "Data": { "Source": "main.c", "Includes": ["main.h"], ... } }`.
+/// Only the strings after `"Source"` and inside of `"Includes"` are of interest to us.
+fn parse_source_dependencies(contents: &str) -> Option<Vec<PathBuf>> {
+    let mut prerequisites = Vec::<PathBuf>::new();
+
+    let source = &contents[contents.find("\"Source\"")? + 8..];
+    let (path, _) = parse_json_string(&source[source.find('"')?..])?;
+    prerequisites.push(PathBuf::from(path));
+
+    let mut includes = &contents[contents.find("\"Includes\"")? + 10..];
+    includes = &includes[includes.find('[')? + 1..];
+    loop {
+        includes = includes.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if !includes.starts_with('"') {
+            break;
+        }
+        let (path, rest) = parse_json_string(includes)?;
+        prerequisites.push(PathBuf::from(path));
+        includes = rest;
+    }
+
+    Some(prerequisites)
+}
+
+/// Reads the JSON string at the start of `json`, returning it unescaped along with
+/// everything after it.
+pub fn parse_json_string(json: &str) -> Option<(String, &str)> {
+    if !json.starts_with('"') {
+        return None;
+    }
+    let mut string = String::new();
+    let mut chars = json.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((string, &json[index + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                // `\\`, `\"` and `\/` stand for themselves, and paths contain no other escapes
+                c => string.push(c),
+            },
+            _ => string.push(c),
+        }
+    }
+    None
+}
+
+/// Returns true when `output` was produced by `command` and is newer than every input.
+pub fn is_up_to_date(output: &Path, command: &CommandLine, inputs: &[PathBuf]) -> bool {
+    let output_time = match modified(output) {
+        Some(time) => time,
+        None => return false,
+    };
+
+    // The flags must be exactly the same as last time
+    let mut previous = String::new();
+    match File::open(command_path(output)) {
+        Ok(mut file) => if file.read_to_string(&mut previous).is_err() {
+            return false;
+        },
+        Err(_) => return false,
+    }
+    if previous != command.display() {
+        return false;
+    }
+
+    // A missing input (like a deleted header) also means we have to rebuild
+    inputs.iter().all(|input| match modified(input) {
+        Some(time) => time <= output_time,
+        None => false,
+    })
+}
+
+/// Returns true when `object` does not need to be recompiled with `command`.
+pub fn object_is_up_to_date(object: &Path, command: &CommandLine) -> bool {
+    match parse_depfile(&depfile_path(object)) {
+        Some(inputs) => is_up_to_date(object, command, &inputs),
+        None => false,
+    }
+}
+
+/// Remembers the command that produced `output` for the next build.
+pub fn record_command(output: &Path, command: &CommandLine) -> ::std::io::Result<()> {
+    let mut file = File::create(command_path(output))?;
+    file.write_all(command.display().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::time::Duration;
+
+    /// A fresh directory for a single test to write its files into.
+    fn scratch(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("maid-cache-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Writes a file last modified `age` seconds ago.
+    fn write(path: &Path, age: u64) {
+        let file = File::create(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+    }
+
+    fn compile(source: &str) -> CommandLine {
+        let mut command = CommandLine::new("gcc");
+        command.args(vec!["-c", source, "-o", "main.c.o"]);
+        command
+    }
+
+    #[test]
+    fn make_rule_with_escaped_spaces() {
+        let rule = "obj/my\\ file.c.o: source/my\\ file.c include/a.h\n";
+        assert_eq!(
+            parse_make_rule(rule).unwrap(),
+            vec![PathBuf::from("source/my file.c"), PathBuf::from("include/a.h")]
+        );
+    }
+
+    #[test]
+    fn make_rule_with_line_continuations() {
+        let rule = "main.c.o: source/main.c \\\n  include/a.h \\\r\n  include/b.h\n";
+        assert_eq!(
+            parse_make_rule(rule).unwrap(),
+            vec![
+                PathBuf::from("source/main.c"),
+                PathBuf::from("include/a.h"),
+                PathBuf::from("include/b.h"),
+            ]
+        );
+    }
+
+    #[test]
+    fn make_rule_without_target() {
+        assert_eq!(parse_make_rule("source/main.c\n"), None);
+    }
+
+    #[test]
+    fn msvc_source_dependencies() {
+        let json = r#"{
+    "Version": "1.2",
+    "Data": {
+        "Source": "c:\\my project\\source\\main.c",
+        "ProvidedModule": "",
+        "Includes": [
+            "c:\\my project\\include\\a.h",
+            "c:\\program files\\sdk\\stdio.h"
+        ],
+        "ImportedModules": []
+    }
+}"#;
+        assert_eq!(
+            parse_source_dependencies(json).unwrap(),
+            vec![
+                PathBuf::from("c:\\my project\\source\\main.c"),
+                PathBuf::from("c:\\my project\\include\\a.h"),
+                PathBuf::from("c:\\program files\\sdk\\stdio.h"),
+            ]
+        );
+    }
+
+    #[test]
+    fn msvc_source_dependencies_without_includes() {
+        let json = r#"{"Data": {"Source": "main.c", "Includes": []}}"#;
+        assert_eq!(parse_source_dependencies(json).unwrap(), vec![PathBuf::from("main.c")]);
+    }
+
+    #[test]
+    fn missing_header_forces_rebuild() {
+        let directory = scratch("missing-header");
+        let source = directory.join("main.c");
+        let header = directory.join("main.h");
+        let object = directory.join("main.c.o");
+        write(&source, 20);
+        write(&header, 20);
+        write(&object, 10);
+
+        let command = compile("main.c");
+        record_command(&object, &command).unwrap();
+        assert!(is_up_to_date(&object, &command, &[source.clone(), header.clone()]));
+
+        fs::remove_file(&header).unwrap();
+        assert!(!is_up_to_date(&object, &command, &[source, header]));
+    }
+
+    #[test]
+    fn changed_command_forces_rebuild() {
+        let directory = scratch("changed-command");
+        let source = directory.join("main.c");
+        let object = directory.join("main.c.o");
+        write(&source, 20);
+        write(&object, 10);
+
+        let command = compile("main.c");
+        record_command(&object, &command).unwrap();
+        assert!(is_up_to_date(&object, &command, &[source.clone()]));
+
+        let mut changed = command.clone();
+        changed.arg("-O2");
+        assert!(!is_up_to_date(&object, &changed, &[source]));
+    }
+
+    #[test]
+    fn newer_input_forces_rebuild() {
+        let directory = scratch("newer-input");
+        let source = directory.join("main.c");
+        let object = directory.join("main.c.o");
+        write(&object, 20);
+        write(&source, 10);
+
+        let command = compile("main.c");
+        record_command(&object, &command).unwrap();
+        assert!(!is_up_to_date(&object, &command, &[source]));
+    }
+}
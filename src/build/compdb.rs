@@ -0,0 +1,255 @@
+//! Writes `compile_commands.json`, the compilation database editors and tools like
+//! clangd and clang-tidy read to learn how every source file is compiled.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use command::CommandLine;
+use utils;
+use super::cache::parse_json_string;
+
+/// How a single source file is compiled.
+struct Entry {
+    file: PathBuf,
+    arguments: Vec<String>,
+    output: PathBuf,
+}
+
+/// Every compile command of a build, in the order they were generated, along with those
+/// of earlier builds.
+#[derive(Default)]
+pub struct Database {
+    entries: Vec<Entry>,
+    /// The entries already in the file. A build may only compile some of the projects,
+    /// like a single member of a workspace, and the others must not disappear from it.
+    previous: Vec<Entry>,
+}
+
+/// Writes `string` as a JSON string, with quotes.
+fn json_string(string: &str) -> String {
+    let mut json = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Skips the whitespace and commas between the values of a JSON object or list.
+fn skip_separators(json: &str) -> &str {
+    json.trim_start_matches(|c: char| c.is_whitespace() || c == ',')
+}
+
+/// Reads the entry at the start of `json`, as written by `Database::save`, returning it
+/// along with everything after it.
+fn parse_entry(json: &str) -> Option<(Entry, &str)> {
+    let mut rest = json.strip_prefix('{')?;
+    let mut file = None;
+    let mut arguments = None;
+    let mut output = None;
+
+    loop {
+        rest = skip_separators(rest);
+        if let Some(after) = rest.strip_prefix('}') {
+            rest = after;
+            break;
+        }
+
+        let (key, after) = parse_json_string(rest)?;
+        rest = after.trim_start().strip_prefix(':')?.trim_start();
+
+        if let Some(after) = rest.strip_prefix('[') {
+            rest = after;
+            let mut list = Vec::<String>::new();
+            loop {
+                rest = skip_separators(rest);
+                if let Some(after) = rest.strip_prefix(']') {
+                    rest = after;
+                    break;
+                }
+                let (item, after) = parse_json_string(rest)?;
+                list.push(item);
+                rest = after;
+            }
+            if key == "arguments" {
+                arguments = Some(list);
+            }
+        } else {
+            let (value, after) = parse_json_string(rest)?;
+            rest = after;
+            match key.as_str() {
+                "file" => file = Some(PathBuf::from(value)),
+                "output" => output = Some(PathBuf::from(value)),
+                _ => {}
+            }
+        }
+    }
+
+    let entry = Entry {
+        file: file?,
+        arguments: arguments?,
+        output: output?,
+    };
+    Some((entry, rest))
+}
+
+/// Reads every entry of a database written by `Database::save`.
+fn parse_entries(json: &str) -> Option<Vec<Entry>> {
+    let mut entries = Vec::<Entry>::new();
+    let mut rest = json.trim_start().strip_prefix('[')?;
+    loop {
+        rest = skip_separators(rest);
+        if rest.starts_with(']') {
+            return Some(entries);
+        }
+        let (entry, after) = parse_entry(rest)?;
+        entries.push(entry);
+        rest = after;
+    }
+}
+
+impl Database {
+    /// Starts a database with the entries already in `path`, which this build replaces as
+    /// it compiles their files again. A file that cannot be read is started over.
+    pub fn open(path: &Path) -> Database {
+        let mut contents = String::new();
+        let previous = match File::open(path).and_then(|mut file| file.read_to_string(&mut contents)) {
+            Ok(_) => parse_entries(&contents).unwrap_or_default(),
+            Err(_) => vec![],
+        };
+
+        Database {
+            entries: vec![],
+            // The entries of deleted sources would otherwise stay forever
+            previous: previous.into_iter().filter(|entry| entry.file.is_file()).collect(),
+        }
+    }
+
+    /// Records the command compiling `file` into `output`.
+    pub fn add(&mut self, file: &Path, command: &CommandLine, output: &Path) {
+        // A project may be built more than once, like a library many projects depend on
+        if self.entries.iter().any(|entry| entry.output == output) {
+            return;
+        }
+
+        self.entries.push(Entry {
+            file: file.to_owned(),
+            arguments: command.to_vec(),
+            output: output.to_owned(),
+        });
+    }
+
+    /// Writes the database to `path`, unless it already has the same contents. Editors
+    /// watching the file would otherwise reload it after every build.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        // Every path in the commands is relative to where we run the compiler
+        let directory = env::current_dir()?;
+
+        // The earlier entries keep their place, with the commands of this build, and the
+        // files compiled for the first time come after them
+        let mut entries = Vec::<&Entry>::new();
+        for previous in &self.previous {
+            match self.entries.iter().find(|entry| entry.output == previous.output) {
+                Some(entry) => entries.push(entry),
+                None => entries.push(previous),
+            }
+        }
+        for entry in &self.entries {
+            if !self.previous.iter().any(|previous| previous.output == entry.output) {
+                entries.push(entry);
+            }
+        }
+
+        let mut json = String::from("[\n");
+        for (index, entry) in entries.iter().enumerate() {
+            let arguments: Vec<String> = entry
+                .arguments
+                .iter()
+                .map(|argument| json_string(argument))
+                .collect();
+
+            json.push_str("  {\n");
+            json.push_str(&format!(
+                "    \"directory\": {},\n",
+                json_string(&directory.to_string_lossy())
+            ));
+            json.push_str(&format!("    \"arguments\": [{}],\n", arguments.join(", ")));
+            json.push_str(&format!(
+                "    \"file\": {},\n",
+                json_string(&entry.file.to_string_lossy())
+            ));
+            json.push_str(&format!(
+                "    \"output\": {}\n",
+                json_string(&entry.output.to_string_lossy())
+            ));
+            json.push_str(if index + 1 < entries.len() {
+                "  },\n"
+            } else {
+                "  }\n"
+            });
+        }
+        json.push_str("]\n");
+
+        utils::write_if_changed(path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn compile(source: &Path, output: &Path) -> CommandLine {
+        let mut command = CommandLine::new("gcc");
+        command.arg("-c").path(source).arg("-o").path(output);
+        command
+    }
+
+    #[test]
+    fn entries_of_earlier_builds_are_kept() {
+        let directory = env::temp_dir().join(format!("maid-compdb-{}", ::std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("compile_commands.json");
+        let sources: Vec<PathBuf> = ["a.c", "b \"quoted\".c", "c.c"]
+            .iter()
+            .map(|name| directory.join(name))
+            .collect();
+        for source in &sources {
+            File::create(source).unwrap();
+        }
+        let output = |source: &PathBuf| source.with_extension("o");
+
+        let mut database = Database::open(&path);
+        database.add(&sources[0], &compile(&sources[0], &output(&sources[0])), &output(&sources[0]));
+        database.add(&sources[1], &compile(&sources[1], &output(&sources[1])), &output(&sources[1]));
+        database.save(&path).unwrap();
+
+        // Building only `c.c` and `a.c` again replaces `a.c` in its place, and keeps `b.c`
+        let mut database = Database::open(&path);
+        let mut changed = compile(&sources[0], &output(&sources[0]));
+        changed.arg("-O2");
+        database.add(&sources[2], &compile(&sources[2], &output(&sources[2])), &output(&sources[2]));
+        database.add(&sources[0], &changed, &output(&sources[0]));
+        database.save(&path).unwrap();
+
+        let entries = Database::open(&path).previous;
+        let files: Vec<&PathBuf> = entries.iter().map(|entry| &entry.file).collect();
+        assert_eq!(files, vec![&sources[0], &sources[1], &sources[2]]);
+        assert_eq!(entries[0].arguments, changed.to_vec());
+        assert_eq!(entries[1].arguments, compile(&sources[1], &output(&sources[1])).to_vec());
+
+        // Deleted sources are dropped
+        fs::remove_file(&sources[1]).unwrap();
+        assert_eq!(Database::open(&path).previous.len(), 2);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,6 +1,9 @@
-use project::Project;
-use super::{CompilerOptions, CompilerTrait, Language};
-use utils;
+use std::path::{Path, PathBuf};
+use command::CommandLine;
+use project::{Project, Target, Warnings};
+use super::{cache, CompilerOptions, CompilerTrait, Language, Os};
+use super::standard::Standard;
+use super::toolchain::Toolchain;
 
 pub struct GCC;
 
@@ -10,96 +13,195 @@ impl CompilerTrait for GCC {
     }
 
     fn exists() -> bool {
-        utils::shell_command_exists("gcc -v")
+        CommandLine::new("gcc").arg("-v").succeeds()
     }
 
-    fn generate_command(project: Project, compiler_options: CompilerOptions) -> String {
-        let mut command = String::new();
+    fn toolchain() -> Toolchain {
+        Toolchain {
+            cc: String::from("gcc"),
+            cxx: String::from("g++"),
+            ar: String::from("ar"),
+            ld: None,
+        }
+    }
+
+    fn supports_standard(toolchain: &Toolchain, standard: &Standard) -> bool {
+        // Preprocess nothing, which fails when the standard is not recognized
+        let language = match standard.language {
+            Language::Cpp => "c++",
+            _ => "c",
+        };
+        CommandLine::configured(toolchain.compiler(&standard.language))
+            .args(vec!["-x", language])
+            .arg(format!("-std={}", standard.name))
+            .args(vec!["-E", "-"])
+            .succeeds()
+    }
+
+    fn generate_command(
+        project: &Project,
+        compiler_options: &CompilerOptions,
+        source: &Path,
+        object: &Path,
+    ) -> CommandLine {
+        // Compiler name, by the language of this source alone. GNU assembly is handed
+        // to the C driver, which knows what to do with it.
+        let language = Language::of(source).unwrap();
+        let mut command = CommandLine::configured(compiler_options.toolchain.compiler(&language));
 
-        // Compiler name
-        match compiler_options.language {
-            Language::C => command.push_str("gcc"),
-            Language::Cpp => command.push_str("g++"),
+        // Compile the source only, leaving linking for later
+        command.arg("-c").path(source).arg("-o").path(object);
+
+        // The headers of the platform we cross compile for
+        if let Some(ref sysroot) = compiler_options.sysroot {
+            command.arg(format!("--sysroot={}", sysroot.display()));
         }
 
-        // Sources
-        for source in compiler_options.sources {
-            command.push_str(format!(" {}", source).as_str());
+        // Have GCC list the headers this object depends on, so we know when to recompile it
+        command
+            .args(vec!["-MMD", "-MF"])
+            .path(&cache::depfile_path(object));
+
+        if let Some(standard) = compiler_options.standard(&language) {
+            command.arg(format!("-std={}", standard.name));
         }
 
-        if cfg!(target_os = "windows") {
-            if compiler_options.release {
-                command.push_str(
-                    format!(" -o ./target/release/{}.exe", project.package.name).as_str(),
-                );
-            } else {
-                command
-                    .push_str(format!(" -o ./target/debug/{}.exe", project.package.name).as_str());
-            }
-        } else {
-            if compiler_options.release {
-                command.push_str(format!(" -o ./target/release/{}", project.package.name).as_str());
-            } else {
-                command.push_str(format!(" -o ./target/debug/{}", project.package.name).as_str());
-            }
+        // Code in shared libraries must not depend on where it is loaded
+        if project.package.target == Target::Dynamic && compiler_options.os() != Os::Windows {
+            command.arg("-fPIC");
         }
 
         // Warnings
-        command.push_str(" -w");
+        if let Some(ref build) = project.build {
+            match build.warnings {
+                Some(Warnings::All) => {
+                    command.arg("-Wall");
+                }
+                Some(Warnings::Extra) => {
+                    command.args(vec!["-Wall", "-Wextra"]);
+                }
+                Some(Warnings::None) => {
+                    command.arg("-w");
+                }
+                Some(Warnings::Default) | None => {}
+            }
+            if build.warnings_as_errors == Some(true) {
+                command.arg("-Werror");
+            }
+        }
 
-        // Optimizations and warnings
-        if compiler_options.release {
-            command.push_str(" -O3");
+        // Optimizations and debug information, as the profile asks for
+        let profile = &compiler_options.profile;
+        command.arg(format!("-O{}", profile.opt_level));
+        if profile.debug {
+            command.arg("-g");
+        }
+        if profile.lto {
+            command.arg("-flto");
         }
 
         // Preprocessor
-        command.push_str(
-            format!(
-                " -D MAID_PACKAGE_NAME=\"{}\" -D MAID_PACKAGE_VERSION=\"{}\"",
-                project.package.name, project.package.version
-            ).as_str(),
-        );
-        if compiler_options.release {
-            command.push_str(" -D MAID_RELEASE");
-        } else {
-            command.push_str(" -D MAID_DEBUG");
-        }
-
-        if let Some(dependencies) = project.dependencies {
-            // Header search directories
-            match dependencies.header_search_directories {
-                Some(directories) => for directory in directories {
-                    command.push_str(format!(" -I {}", directory).as_str());
-                },
-                None => {}
+        command
+            .arg("-D")
+            .arg(format!("MAID_PACKAGE_NAME=\"{}\"", project.package.name))
+            .arg("-D")
+            .arg(format!("MAID_PACKAGE_VERSION=\"{}\"", project.package.version));
+        for define in &profile.defines {
+            command.arg("-D").arg(define.as_str());
+        }
+        command.args(&profile.flags);
+
+        // Header search directories
+        for directory in &compiler_options.dependencies.header_search_directories {
+            command.arg("-I").path(directory);
+        }
+
+        // Anything else our dependencies need, like pkg-config's `-pthread`
+        command.args(&compiler_options.dependencies.compile_flags);
+
+        if let Some(ref build) = project.build {
+            if let Some(ref options) = build.gnu_options {
+                command.args(options);
             }
+        }
 
-            // Linker search directories
-            match dependencies.linker_search_directories {
-                Some(directories) => for directory in directories {
-                    command.push_str(format!(" -L {}", directory).as_str());
-                },
-                None => {}
+        command
+    }
+
+    fn generate_link_command(
+        project: &Project,
+        compiler_options: &CompilerOptions,
+        objects: &[PathBuf],
+        output: &Path,
+    ) -> CommandLine {
+        // Static libraries are just an archive of every object
+        if project.package.target == Target::Static {
+            let mut command = CommandLine::configured(&compiler_options.toolchain.ar);
+            command.arg("rcs").path(output);
+            for object in objects {
+                command.path(object);
             }
+            return command;
+        }
+
+        // Linker driver, which knows where the C runtime is and links it for us
+        let toolchain = &compiler_options.toolchain;
+        let mut command = CommandLine::configured(toolchain.compiler(&compiler_options.language));
+        if let Some(fuse_ld) = toolchain.fuse_ld() {
+            command.arg(fuse_ld);
+        }
+
+        // Objects
+        for object in objects {
+            command.path(object);
+        }
+
+        command.arg("-o").path(output);
 
-            // The "linker search directories" are just used to point to a directory where the following
-            // "link library" name is passed. For example, in the directory `./SDL2/lib`, there may be a file
-            // called "libSDL2.lib", and you have one "link library", called "SDL2", so " -lSDL2". The linker
-            // finds the file "libSDL2.lib", in the "linker search directory" (" -L ./SDL2/lib").
-            match dependencies.link_libraries {
-                Some(libraries) => for name in libraries {
-                    command.push_str(format!(" -l{}", name).as_str());
-                },
-                None => {}
+        if let Some(ref sysroot) = compiler_options.sysroot {
+            command.arg(format!("--sysroot={}", sysroot.display()));
+        }
+
+        if project.package.target == Target::Dynamic {
+            command.arg("-shared");
+
+            // Programs record the soname instead of the file name, so they keep working
+            // with any later library of the same major version
+            match (compiler_options.os(), project.soname(compiler_options.os())) {
+                (Os::MacOS, Some(soname)) => {
+                    command.arg(format!("-Wl,-install_name,@rpath/{}", soname));
+                }
+                (_, Some(soname)) => {
+                    command.arg(format!("-Wl,-soname,{}", soname));
+                }
+                (_, None) => {}
             }
         }
 
-        if let Some(build) = project.build {
-            match build.gnu_options {
-                Some(options) => for option in options {
-                    command.push_str(format!(" {}", option).as_str());
-                },
-                None => {}
+        // Link-time optimization and flags like `-fsanitize=address` must be given to the linker too
+        if compiler_options.profile.lto {
+            command.arg("-flto");
+        }
+        command.args(&compiler_options.profile.flags);
+
+        // Linker search directories
+        for directory in &compiler_options.dependencies.linker_search_directories {
+            command.arg("-L").path(directory);
+        }
+
+        // The "linker search directories" are just used to point to a directory where the following
+        // "link library" name is passed. For example, in the directory `./SDL2/lib`, there may be a file
+        // called "libSDL2.lib", and you have one "link library", called "SDL2", so " -lSDL2". The linker
+        // finds the file "libSDL2.lib", in the "linker search directory" (" -L ./SDL2/lib").
+        for name in &compiler_options.dependencies.link_libraries {
+            command.arg(format!("-l{}", name));
+        }
+
+        command.args(&compiler_options.dependencies.link_flags);
+
+        if let Some(ref build) = project.build {
+            if let Some(ref options) = build.gnu_options {
+                command.args(options);
             }
         }
 
@@ -0,0 +1,48 @@
+//! Runs compiler invocations across a pool of worker threads.
+//!
+//! Each invocation's output is captured, and printed in one piece once it finishes,
+//! so diagnostics from different files never interleave on the terminal.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use command::CommandLine;
+use utils;
+use super::cache;
+
+/// A single command producing a single file, like compiling one object.
+pub struct Job {
+    pub command: CommandLine,
+    pub output: PathBuf,
+}
+
+/// Runs every job on at most `workers` threads, returning false if any of them failed.
+/// Once a job fails, no new jobs are started, but the ones already running are finished.
+pub fn run(jobs: Vec<Job>, workers: usize, verbose: bool) -> bool {
+    // Held while printing, so a job's output is written all at once
+    let print_lock = Mutex::new(());
+
+    utils::parallel(jobs, workers, move |job| {
+        let result = job.command.output();
+
+        let _guard = print_lock.lock().unwrap();
+        if verbose {
+            eprintln!("{}", job.command.display());
+        }
+        match result {
+            Ok(output) => {
+                io::stdout().write_all(&output.stdout).unwrap();
+                io::stderr().write_all(&output.stderr).unwrap();
+
+                if output.status.success() {
+                    cache::record_command(&job.output, &job.command).unwrap();
+                }
+                output.status.success()
+            }
+            Err(e) => {
+                eprintln!("Failed to execute `{}`: {}", job.command.display(), e);
+                false
+            }
+        }
+    })
+}
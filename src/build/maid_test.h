@@ -0,0 +1,55 @@
+/*
+ * maid_test.h: test cases that live beside the code they test.
+ *
+ *     #include <maid_test.h>
+ *
+ *     MAID_TEST(adds_numbers) {
+ *         MAID_ASSERT_EQ(add(2, 2), 4);
+ *     }
+ *
+ * `maid test` finds every MAID_TEST in source/ and tests/, generates a main running
+ * them, and reports each one on its own. In every other build, the test cases are
+ * compiled but never called.
+ *
+ * This file is written by maid, and overwritten on every build.
+ */
+
+#ifndef MAID_TEST_H
+#define MAID_TEST_H
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+#define MAID_TEST_EXTERN extern "C"
+#else
+#define MAID_TEST_EXTERN
+#endif
+
+#ifdef MAID_TESTING
+/* The generated main calls every test case by name, so they have to be visible to it */
+#define MAID_TEST(name)                           \
+    MAID_TEST_EXTERN void maid_test_##name(void); \
+    MAID_TEST_EXTERN void maid_test_##name(void)
+#else
+#define MAID_TEST(name) static inline void maid_test_##name(void)
+#endif
+
+/* Ends the test case that is running, as a failure. */
+static inline void maid_test_fail(const char *file, int line, const char *message)
+{
+    fprintf(stderr, "%s:%d: assertion failed: %s\n", file, line, message);
+    exit(1);
+}
+
+#define MAID_ASSERT(condition)                                   \
+    do {                                                         \
+        if (!(condition)) {                                      \
+            maid_test_fail(__FILE__, __LINE__, #condition);      \
+        }                                                        \
+    } while (0)
+
+#define MAID_ASSERT_EQ(left, right) MAID_ASSERT((left) == (right))
+#define MAID_ASSERT_NE(left, right) MAID_ASSERT((left) != (right))
+
+#endif
@@ -3,34 +3,189 @@
 
 mod gcc;
 mod clang;
-
-use std::fs::DirBuilder;
-use std::path::Path;
-use project::Project;
+mod msvc;
+mod assembler;
+mod cache;
+mod compdb;
+mod jobs;
+mod profile;
+pub mod programs;
+mod standard;
+mod toolchain;
+mod triple;
+
+pub use self::profile::Profile;
+pub use self::programs::Program;
+pub use self::compdb::Database;
+use self::standard::Standard;
+use self::toolchain::Toolchain;
+pub use self::triple::{Os, Triple};
+
+use std::collections::BTreeMap;
+use std::fs::{self, DirBuilder};
+use std::path::{Path, PathBuf};
+use command::CommandLine;
+use dependencies::{self, ResolvedDependencies};
+use project::{executable_name, Binary, Project, Target, Workspace};
 use user::Config;
 use utils;
 use ansi_term::Color::Green;
 
-pub fn build(release: bool, verbose: bool) -> Result<(), CompileError> {
-    let project = match Project::get() {
-        Ok(project) => project,
-        Err(e) => {
-            return Err(CompileError {
-                error_type: CompileErrorType::CouldNotLocateProjectFile,
-                msg: e.description,
-            })
+/// The projects built so far during a build, by the canonical path of their root, along
+/// with the dependencies anything linking against them also needs.
+pub type Built = BTreeMap<PathBuf, ResolvedDependencies>;
+
+/// What the user asked for on the command line, shared by the project and all of its dependencies.
+#[derive(Clone)]
+pub struct BuildOptions {
+    pub profile: Profile,
+    pub verbose: bool,
+    /// The number of compiler invocations to run at the same time.
+    pub jobs: usize,
+    /// The `target/` directory of the workspace, which every project is built into.
+    pub target_directory: PathBuf,
+    /// The platform to cross compile for, or `None` to build for this one.
+    pub triple: Option<Triple>,
+    /// The only executable to link, like with `--bin`, or `None` to link all of them.
+    pub binary: Option<String>,
+}
+
+impl BuildOptions {
+    /// The directory every binary is written to, like `./target/debug`, or
+    /// `./target/aarch64-linux-gnu/debug` when cross compiling.
+    pub fn output_directory(&self) -> PathBuf {
+        match self.triple {
+            Some(ref triple) => self
+                .target_directory
+                .join(&triple.name)
+                .join(&self.profile.name),
+            None => self.target_directory.join(&self.profile.name),
         }
-    };
+    }
+
+    /// The operating system the binaries are built for.
+    pub fn os(&self) -> Os {
+        match self.triple {
+            Some(ref triple) => triple.os,
+            None => Os::host(),
+        }
+    }
+}
+
+/// Builds every project of the workspace, after building their dependencies.
+///
+/// Returns the dependencies of every member, in the same order as `workspace.members`.
+pub fn build(
+    workspace: &Workspace,
+    options: &BuildOptions,
+) -> Result<Vec<ResolvedDependencies>, CompileError> {
+    let mut database = Database::open(&database_path(workspace));
+    let mut built = Built::new();
+    let mut dependencies = Vec::<ResolvedDependencies>::new();
+    for project in &workspace.members {
+        let root = project.root.canonicalize().unwrap_or_else(|_| project.root.clone());
+        dependencies.push(build_project(
+            project,
+            options,
+            &mut vec![root],
+            &mut built,
+            &mut database,
+        )?);
+    }
+
+    finish(workspace, options, &database);
+    Ok(dependencies)
+}
+
+/// Where the compilation database of `workspace` is written.
+pub fn database_path(workspace: &Workspace) -> PathBuf {
+    workspace.root.join("compile_commands.json")
+}
+
+/// Writes the compilation database and lets the user know the build is done.
+fn finish(workspace: &Workspace, options: &BuildOptions, database: &Database) {
+    // Let editors know how every source is compiled, even when nothing had to be
+    database
+        .save(&database_path(workspace))
+        .expect("Failed to write compile_commands.json.");
+
+    println!(
+        "    {} {} {}",
+        Green.paint("Finished"),
+        options.profile.name,
+        options.profile.description()
+    );
+}
+
+/// Builds a single project, building its dependencies first. `parents` holds the roots
+/// of every project currently being built, so that dependency cycles are caught.
+///
+/// A project already in `built`, like a member of the workspace which other members depend
+/// on, is not built again. Every compile command is recorded into `database`.
+///
+/// Returns the dependencies of the project, which anything linking against it also needs.
+pub fn build_project(
+    project: &Project,
+    options: &BuildOptions,
+    parents: &mut Vec<PathBuf>,
+    built: &mut Built,
+    database: &mut Database,
+) -> Result<ResolvedDependencies, CompileError> {
+    let root = project.root.canonicalize().unwrap_or_else(|_| project.root.clone());
+    if let Some(dependencies) = built.get(&root) {
+        return Ok(dependencies.clone());
+    }
+
+    let (compiler, compiler_options) = configure(project, options, parents, built, database)?;
+    let dependencies = compiler_options.dependencies.clone();
+
+    let binary = options.binary.as_ref().map(|binary| binary.as_str());
+    match compiler {
+        Compiler::GNU => compile(gcc::GCC, project, compiler_options, binary, database)?,
+        Compiler::Clang => compile(clang::Clang, project, compiler_options, binary, database)?,
+        Compiler::MSVC => compile(msvc::MSVC, project, compiler_options, binary, database)?,
+    }
+
+    built.insert(root, dependencies.clone());
+    Ok(dependencies)
+}
+
+/// Collects every source file we know how to compile in `directory` and its subdirectories.
+fn collect_sources(directory: &Path) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = utils::get_files_in_directory(directory, true)
+        .into_iter()
+        // Subdirectories are listed too, but we only compile files
+        .filter(|path| path.is_file() && Language::of(path).is_some())
+        .collect();
+
+    // Keep the order stable between builds, so the link command does not change for nothing
+    sources.sort();
+    sources
+}
+
+/// Gets everything ready to compile a project: builds its dependencies, runs its build.py,
+/// collects its sources, and decides which compiler to use.
+fn configure(
+    project: &Project,
+    options: &BuildOptions,
+    parents: &mut Vec<PathBuf>,
+    built: &mut Built,
+    database: &mut Database,
+) -> Result<(Compiler, CompilerOptions), CompileError> {
+    let mut dependencies = dependencies::resolve(project, options, parents, built, database)?;
 
     // If this project has a build.py file but does not specifically have
     // Python as its target configuration, we just execute the file and continue
     // building.
-    if Path::new("./build.py").exists() {
-        if verbose {
+    let build_script = project.root.join("build.py");
+    if build_script.exists() {
+        if options.verbose {
             eprintln!("Executing build.py...");
         }
 
-        if utils::shell_command("python ./build.py", false)
+        if CommandLine::new("python")
+            .path(&build_script)
+            .status()
             .expect("Failed to execute Python.")
             .success() == false
         {
@@ -44,130 +199,331 @@ pub fn build(release: bool, verbose: bool) -> Result<(), CompileError> {
     let mut dir_builder = DirBuilder::new();
     // Recursive enables us to not get an error if the directory exists
     dir_builder.recursive(true);
-    if release {
-        // Make the release folder
-        dir_builder.create("./target/release").unwrap();
-    } else {
-        // Make the debug folder
-        dir_builder.create("./target/debug").unwrap();
-    }
+    // Make the debug or release folder
+    let output_directory = options.output_directory();
+    dir_builder.create(&output_directory).unwrap();
 
     // Get the source folder path
-    let source_dir = Path::new("./source");
+    let source_dir = project.root.join("source");
     // Ensure the path is correct, and that it is a directory
-    assert!(source_dir.is_dir());
+    if !source_dir.is_dir() {
+        return Err(CompileError {
+            error_type: CompileErrorType::SourceDirectoryNotFound,
+            msg: format!("`{}` is not a directory.", source_dir.display()),
+        });
+    }
 
     // The path to every source file in source/
-    let mut sources = Vec::<String>::new();
-    let mut main_extension = String::new();
-
-    // This is where we get our source files
-    for path in utils::get_files_in_directory(source_dir, true) {
-        let ext = path.extension().unwrap();
-        if path.file_stem().unwrap().to_str() == Some("main") {
-            main_extension = ext.to_str().unwrap().to_owned(); // Obtain the extension of our main source file
-            sources.push(path.to_str().unwrap().to_owned()); // Push the main source file
-        } else {
-            // When the file is not main
-            if ext == "c" || ext == "cc" || ext == "cxx" || ext == "cpp" {
-                // Push the source file (as long as it is a recognized source file)
-                sources.push(path.to_str().unwrap().to_owned());
-            }
+    let mut sources = collect_sources(&source_dir);
+    let binaries = match project.binaries() {
+        Ok(binaries) => binaries,
+        Err(e) => {
+            return Err(CompileError {
+                error_type: CompileErrorType::DuplicateBinary,
+                msg: e.description,
+            })
         }
-    }
+    };
 
-    // Determine the main language used in the project
-    let language: Language;
-    match main_extension.as_str() {
-        "c" => language = Language::C,
+    if project.package.target == Target::Executable && binaries.is_empty() {
+        return Err(CompileError {
+            error_type: CompileErrorType::FileTypeOfMainNotRecognized,
+            msg: "File extension of 'main' in './source/' does not match C, C++, or assembly.".to_string(),
+        });
+    }
 
-        "cc" => language = Language::Cpp,
-        "cxx" => language = Language::Cpp,
-        "cpp" => language = Language::Cpp,
+    // A source in source/bin/ replaced by a [[bin]] of the same name is not built at all,
+    // as it would otherwise be linked into every executable, along with its `main`
+    let bin_dir = source_dir.join("bin");
+    sources.retain(|source| {
+        source.parent() != Some(bin_dir.as_path()) || binaries.iter().any(|binary| binary.source == *source)
+    });
 
-        _ => {
+    // A [[bin]] may be anywhere in the project, not just in source/
+    for binary in &binaries {
+        if !binary.source.is_file() || Language::of(&binary.source).is_none() {
             return Err(CompileError {
                 error_type: CompileErrorType::FileTypeOfMainNotRecognized,
-                msg: "File extension of 'main' in './source/' does not match C or C++.".to_string(),
-            })
+                msg: format!(
+                    "The source of the `{}` binary, `{}`, is not a C, C++, or assembly file.",
+                    binary.name,
+                    binary.source.display()
+                ),
+            });
+        }
+        if !sources.contains(&binary.source) {
+            sources.push(binary.source.clone());
         }
     }
 
-    let compiler_options = CompilerOptions {
-        release: release,
-        verbose: verbose,
-        sources: sources,
-        language: language,
+    // Any source may include maid_test.h, even outside of `maid test`. Only those projects
+    // get its directory, so everything else is compiled the same as without maid.
+    if sources.iter().any(|source| programs::uses_test_header(source)) {
+        programs::add_test_header(&mut dependencies, options);
+    }
+
+    // Every language used by the sources, in order to check the standards against them
+    let mut languages = Vec::<Language>::new();
+    for source in &sources {
+        let language = Language::of(source).unwrap();
+        if !languages.contains(&language) {
+            languages.push(language);
+        }
+    }
+
+    // C++ objects need the C++ runtime, so we link with the C++ driver as soon as there is one
+    let language = if languages.contains(&Language::Cpp) {
+        Language::Cpp
+    } else {
+        Language::C
     };
 
+    // Make sure every standard is one for a language the project is written in
+    let standards = match project.package.standard {
+        Some(ref standards) => Standard::validate(&standards.names(), &languages)?,
+        None => vec![],
+    };
+
+    // The user's configuration is only needed when the project leaves something out
+    let config = Config::get();
+    let user_toolchain = match config {
+        Ok(ref config) => config.toolchain.clone(),
+        Err(_) => None,
+    };
+    let target_config = project.target_config(options.triple.as_ref());
+    let configured = toolchain::configured(
+        project,
+        options.triple.as_ref(),
+        target_config,
+        user_toolchain.as_ref(),
+    );
+
     // Set the compiler
-    let compiler: Compiler = match project.build.clone() {
-        Some(build) => {
-            // If the project configuration has a preferred compiler,
-            // then forcefully use it. Otherwise, use the preferred
-            // compiler specified in the user's config file.
-            match build.preferred_compiler {
-                Some(compiler) => compiler,
-                None => match Config::get() {
-                    Ok(config) => config.preferred_compiler,
-                    Err(e) => {
-                        return Err(CompileError {
-                            error_type: CompileErrorType::CouldNotReadUserConfig,
-                            msg: e.to_string(),
-                        })
-                    }
-                },
-            }
-        }
-        None => match Config::get() {
-            Ok(config) => config.preferred_compiler,
-            Err(e) => {
-                return Err(CompileError {
-                    error_type: CompileErrorType::CouldNotReadUserConfig,
-                    msg: e.to_string(),
-                })
-            }
+    let preferred_compiler = match project.build {
+        Some(ref build) => build.preferred_compiler,
+        None => None,
+    };
+    let compiler: Compiler = match preferred_compiler {
+        // If the project configuration has a preferred compiler, then forcefully use it
+        Some(compiler) => compiler,
+        None => match configured.cc {
+            // Otherwise, the configured C compiler decides which backend we use
+            Some(ref cc) => toolchain::detect_compiler(cc)?,
+            // And with neither, use the preferred compiler in the user's config file
+            None => match config {
+                Ok(config) => config.preferred_compiler,
+                Err(e) => {
+                    return Err(CompileError {
+                        error_type: CompileErrorType::CouldNotReadUserConfig,
+                        msg: e.to_string(),
+                    })
+                }
+            },
         },
     };
 
-    match compiler {
-        Compiler::GNU => compile(gcc::GCC, project, compiler_options),
-        Compiler::Clang => compile(clang::Clang, project, compiler_options),
+    let mut defaults = match compiler {
+        Compiler::GNU => gcc::GCC::toolchain(),
+        Compiler::Clang => clang::Clang::toolchain(),
+        Compiler::MSVC => msvc::MSVC::toolchain(),
+    };
+
+    // A GCC cross toolchain is a separate set of programs, like `aarch64-linux-gnu-gcc`,
+    // whereas Clang is told the target with `--target=`.
+    if let Some(ref triple) = options.triple {
+        if compiler == Compiler::GNU {
+            let prefix = match target_config.and_then(|config| config.prefix.clone()) {
+                Some(prefix) => prefix,
+                None => format!("{}-", triple.name),
+            };
+            defaults = defaults.with_prefix(&prefix);
+        }
     }
+
+    let compiler_options = CompilerOptions {
+        profile: options.profile.clone(),
+        verbose: options.verbose,
+        jobs: options.jobs,
+        object_directory: output_directory.join("obj").join(&project.package.name),
+        output_directory: output_directory,
+        source_directory: source_dir,
+        sources: sources,
+        binaries: binaries,
+        language: language,
+        standards: standards,
+        toolchain: Toolchain::resolve(&configured, defaults),
+        triple: options.triple.clone(),
+        sysroot: target_config
+            .and_then(|config| config.sysroot.as_ref())
+            .map(|sysroot| project.path(sysroot)),
+        dependencies: dependencies,
+    };
+
+    // cl.exe has no idea what GNU assembly is
+    if compiler == Compiler::MSVC && languages.contains(&Language::Asm) {
+        return Err(CompileError {
+            error_type: CompileErrorType::SourceTypeNotSupported,
+            msg: "MSVC cannot build GNU assembly (`.s` and `.S`) sources.".to_string(),
+        });
+    }
+
+    Ok((compiler, compiler_options))
 }
 
 /// A high-level interface for compiler options.
 #[derive(Clone)]
 pub struct CompilerOptions {
-    pub release: bool,
+    pub profile: Profile,
     pub verbose: bool,
-    pub sources: Vec<String>,
+    /// The number of compiler invocations to run at the same time.
+    pub jobs: usize,
+    /// The directory the final binary is written to, like `./target/debug`.
+    pub output_directory: PathBuf,
+    /// The directory every object file is written to, like `./target/debug/obj/hello`.
+    /// Each project has its own, as projects of a workspace share `target/`.
+    pub object_directory: PathBuf,
+    pub source_directory: PathBuf,
+    pub sources: Vec<PathBuf>,
+    /// The executables to link, each from one of `sources`.
+    pub binaries: Vec<Binary>,
+    /// The language of the final link, which is C++ whenever any source is C++.
     pub language: Language,
+    /// At most one standard for each language.
+    pub standards: Vec<Standard>,
+    /// The programs to run, like `gcc-12` instead of `gcc`.
+    pub toolchain: Toolchain,
+    /// The platform to cross compile for, or `None` to build for this one.
+    pub triple: Option<Triple>,
+    /// The headers and libraries of the platform we cross compile for.
+    pub sysroot: Option<PathBuf>,
+    /// Search directories and libraries from the project file and every dependency.
+    pub dependencies: ResolvedDependencies,
     // pub compiler: Compiler,
 }
 
-#[derive(Clone)]
+impl CompilerOptions {
+    /// Maps a source file to its object file, keeping the folder structure of `source/`
+    /// so that `source/net/socket.c` and `source/socket.c` never collide. A [[bin]] from
+    /// outside of `source/` is named after the binary instead, like `bin/gen.o`, so that
+    /// its objects stay in `target/` even for a path like `../tools/gen.c`.
+    pub fn object_path(&self, source: &Path) -> PathBuf {
+        let relative = match source.strip_prefix(&self.source_directory) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => {
+                let name = match self.binaries.iter().find(|binary| binary.source == source) {
+                    Some(binary) => PathBuf::from(&binary.name),
+                    None => PathBuf::from(source.file_name().unwrap()),
+                };
+                Path::new("bin").join(name)
+            }
+        };
+        let mut object = self.object_directory.join(relative).into_os_string();
+        object.push(".o");
+        PathBuf::from(object)
+    }
+
+    /// The operating system the binaries are built for.
+    pub fn os(&self) -> Os {
+        match self.triple {
+            Some(ref triple) => triple.os,
+            None => Os::host(),
+        }
+    }
+
+    /// Returns true when `source` has the `main` of one of the executables, which is only
+    /// linked into that executable.
+    pub fn is_binary(&self, source: &Path) -> bool {
+        self.binaries.iter().any(|binary| binary.source == source)
+    }
+
+    /// The objects of every source but those of the executables, which are linked into
+    /// the library and into every executable.
+    pub fn common_objects(&self, objects: &[PathBuf]) -> Vec<PathBuf> {
+        self.sources
+            .iter()
+            .zip(objects)
+            .filter(|&(source, _)| !self.is_binary(source))
+            .map(|(_, object)| object.clone())
+            .collect()
+    }
+
+    /// Where the dynamic libraries of the project, and of its dependencies, are.
+    pub fn library_directories(&self) -> Vec<PathBuf> {
+        let mut directories = vec![self.output_directory.clone()];
+        for directory in self.dependencies.library_directories() {
+            if !directories.contains(&directory) {
+                directories.push(directory);
+            }
+        }
+        directories
+    }
+
+    /// The standard the sources of `language` are compiled with, if the project has one.
+    pub fn standard(&self, language: &Language) -> Option<&Standard> {
+        self.standards
+            .iter()
+            .find(|standard| standard.language == *language)
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
 pub enum Language {
     C,
     Cpp,
+    /// GNU assembly, which is assembled by the compiler: `.s`, or `.S` to run the preprocessor first.
+    Asm,
+    /// NASM assembly (`.asm`), which is assembled by the assembler in [build].
+    Nasm,
+}
+
+impl Language {
+    /// The language of a source file, judging from its extension.
+    pub fn of(source: &Path) -> Option<Language> {
+        match source.extension().and_then(|ext| ext.to_str()) {
+            Some("c") => Some(Language::C),
+            Some("cc") | Some("cxx") | Some("cpp") => Some(Language::Cpp),
+            Some("s") | Some("S") => Some(Language::Asm),
+            Some("asm") => Some(Language::Nasm),
+            _ => None,
+        }
+    }
+
+    pub fn display(&self) -> &'static str {
+        match *self {
+            Language::C => "C",
+            Language::Cpp => "C++",
+            Language::Asm => "GNU assembly",
+            Language::Nasm => "NASM assembly",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
 pub enum Compiler {
     GNU,
     Clang,
-    // MSVC,
+    MSVC,
 }
 
 pub fn detect_available_compilers() -> Vec<Compiler> {
     let mut compilers = Vec::<Compiler>::new();
 
-    if gcc::GCC::exists() {
+    // A compiler in CC is the one the user wants, even when it is not named `gcc` or `clang`
+    if let Ok(cc) = ::std::env::var("CC") {
+        if let Ok(compiler) = toolchain::detect_compiler(&cc) {
+            compilers.push(compiler);
+        }
+    }
+
+    if gcc::GCC::exists() && !compilers.contains(&Compiler::GNU) {
         compilers.push(Compiler::GNU)
     }
-    if clang::Clang::exists() {
+    if clang::Clang::exists() && !compilers.contains(&Compiler::Clang) {
         compilers.push(Compiler::Clang)
     }
+    if msvc::MSVC::exists() && !compilers.contains(&Compiler::MSVC) {
+        compilers.push(Compiler::MSVC)
+    }
 
     compilers
 }
@@ -175,10 +531,25 @@ pub fn detect_available_compilers() -> Vec<Compiler> {
 #[derive(Debug)]
 pub enum CompileErrorType {
     CompilerReturnedNonZero,
-    CouldNotLocateProjectFile,
+    LinkerReturnedNonZero,
+    SourceDirectoryNotFound,
+    DependencyNotFound,
+    DependencyIsNotALibrary,
+    DependencyCycle,
+    GitCommandFailed,
+    LockfileCouldNotBeParsed,
+    LockfileCouldNotBeWritten,
+    PkgConfigFailed,
     PythonBuildScriptReturnedNonZero,
     FileTypeOfMainNotRecognized,
+    DuplicateBinary,
     CouldNotReadUserConfig,
+    UnknownProfile,
+    UnknownStandard,
+    StandardNotSupported,
+    SourceTypeNotSupported,
+    UnknownToolchain,
+    UnknownTarget,
 }
 
 #[derive(Debug)]
@@ -199,48 +570,203 @@ pub trait CompilerTrait {
     #[inline]
     fn exists() -> bool;
 
-    /// Must generate an entire build command given the information about the project
-    /// and build settings.
-    fn generate_command(project: Project, compiler_options: CompilerOptions) -> String;
+    /// Must return the programs the compiler usually runs, which are used for anything
+    /// not configured by the user.
+    fn toolchain() -> Toolchain;
+
+    /// Must return true when the compiler accepts the given language standard. Older
+    /// compilers do not know about the newest standards, like `c++23`.
+    fn supports_standard(toolchain: &Toolchain, standard: &Standard) -> bool;
+
+    /// Must generate a command that compiles a single source file into the given object
+    /// file, without linking. The command must also make the compiler list every header
+    /// included in `cache::depfile_path(object)`, either as a Make-style dependency file
+    /// or as the JSON of MSVC's `/sourceDependencies`.
+    fn generate_command(
+        project: &Project,
+        compiler_options: &CompilerOptions,
+        source: &Path,
+        object: &Path,
+    ) -> CommandLine;
+
+    /// Must generate a command that links every object file into the final binary at `output`.
+    /// Executables and dynamic libraries are linked, while static libraries are archived.
+    fn generate_link_command(
+        project: &Project,
+        compiler_options: &CompilerOptions,
+        objects: &[PathBuf],
+        output: &Path,
+    ) -> CommandLine;
 }
 
+/// Compiles and links a project. With `only`, that is the only executable linked.
 pub fn compile<T>(
     _: T,
-    project: Project,
+    project: &Project,
     compiler_options: CompilerOptions,
+    only: Option<&str>,
+    database: &mut Database,
 ) -> Result<(), CompileError>
 where
     T: CompilerTrait,
 {
-    let command: String = T::generate_command(project.clone(), compiler_options.clone());
+    let objects = compile_objects::<T>(project, &compiler_options, database)?;
+    link_outputs::<T>(project, &compiler_options, &objects, only)
+}
+
+/// Links the library of a project and its executables, or only the executable named
+/// `only` when there is one.
+fn link_outputs<T: CompilerTrait>(
+    project: &Project,
+    compiler_options: &CompilerOptions,
+    objects: &[PathBuf],
+    only: Option<&str>,
+) -> Result<(), CompileError> {
+    let common = compiler_options.common_objects(objects);
+
+    if project.package.target != Target::Executable {
+        let output = compiler_options
+            .output_directory
+            .join(project.output_name(compiler_options.os()));
+        link::<T>(project, compiler_options, &common, &output)?;
+
+        // What links against a dynamic library looks for it by its soname when it runs
+        if let Some(soname) = project.soname(compiler_options.os()) {
+            let link = compiler_options.output_directory.join(soname);
+            if fs::symlink_metadata(&link).is_ok() {
+                fs::remove_file(&link).unwrap();
+            }
+            utils::symlink(Path::new(&project.output_name(compiler_options.os())), &link)
+                .expect("Failed to link the soname of the library.");
+        }
+    }
+
+    // The executables of a library are linked like those of any other project
+    let mut executable = project.clone();
+    executable.package.target = Target::Executable;
+
+    for binary in &compiler_options.binaries {
+        if only.is_some_and(|name| name != binary.name) {
+            continue;
+        }
 
-    if compiler_options.verbose {
-        eprintln!("{}", command);
+        let mut objects = vec![compiler_options.object_path(&binary.source)];
+        objects.extend(common.iter().cloned());
+        let output = compiler_options
+            .output_directory
+            .join(executable_name(&binary.name, compiler_options.os()));
+        link::<T>(&executable, compiler_options, &objects, &output)?;
     }
 
-    println!(
-        "   {} {} v{} with {}",
-        Green.paint("Compiling"),
-        project.package.name,
-        project.package.version,
-        T::display(),
-    );
+    Ok(())
+}
 
-    // Calling the compiler with our command
-    if utils::shell_command(&command, false)
-        .expect("Failed to query compiler.")
-        .success() == false
-    {
-        return Err(CompileError {
-            error_type: CompileErrorType::CompilerReturnedNonZero,
-            msg: "Compilation terminated due to previous error(s).".to_string(),
+/// Compiles every source of `compiler_options` that is out of date, returning the objects
+/// of all of them.
+fn compile_objects<T: CompilerTrait>(
+    project: &Project,
+    compiler_options: &CompilerOptions,
+    database: &mut Database,
+) -> Result<Vec<PathBuf>, CompileError> {
+    for standard in &compiler_options.standards {
+        if !T::supports_standard(&compiler_options.toolchain, standard) {
+            return Err(CompileError {
+                error_type: CompileErrorType::StandardNotSupported,
+                msg: format!(
+                    "{} does not support the `{}` standard.",
+                    T::display(),
+                    standard.name
+                ),
+            });
+        }
+    }
+
+    let mut dir_builder = DirBuilder::new();
+    dir_builder.recursive(true);
+
+    let mut objects = Vec::<PathBuf>::new();
+    let mut jobs = Vec::<jobs::Job>::new();
+
+    for source in &compiler_options.sources {
+        let object = compiler_options.object_path(source);
+        let command = match Language::of(source) {
+            Some(Language::Nasm) => {
+                assembler::generate_command(project, compiler_options, source, &object)
+            }
+            _ => T::generate_command(project, compiler_options, source, &object),
+        };
+        objects.push(object.clone());
+        database.add(source, &command, &object);
+
+        // Skip every object whose source, headers and flags are unchanged since it was built.
+        // Assembly that is not preprocessed (`.s`) includes nothing, so there is no dependency file.
+        let up_to_date = if source.extension().and_then(|ext| ext.to_str()) == Some("s") {
+            cache::is_up_to_date(&object, &command, &[source.clone()])
+        } else {
+            cache::object_is_up_to_date(&object, &command)
+        };
+        if up_to_date {
+            continue;
+        }
+
+        // Mirror the folder structure of source/ inside of the object directory
+        dir_builder.create(object.parent().unwrap()).unwrap();
+
+        jobs.push(jobs::Job {
+            command: command,
+            output: object,
         });
     }
 
-    if compiler_options.release {
-        println!("    {} release [optimized]", Green.paint("Finished"));
-    } else {
-        println!("    {} debug [unoptimized]", Green.paint("Finished"));
+    if !jobs.is_empty() {
+        println!(
+            "   {} {} v{} with {}",
+            Green.paint("Compiling"),
+            project.package.name,
+            project.package.version,
+            T::display(),
+        );
+
+        // Calling the compiler for every outdated object, across all of our workers
+        if !jobs::run(jobs, compiler_options.jobs, compiler_options.verbose) {
+            return Err(CompileError {
+                error_type: CompileErrorType::CompilerReturnedNonZero,
+                msg: "Compilation terminated due to previous error(s).".to_string(),
+            });
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Links `objects` into `output`, when any of them changed or the output is missing.
+fn link<T: CompilerTrait>(
+    project: &Project,
+    compiler_options: &CompilerOptions,
+    objects: &[PathBuf],
+    output: &Path,
+) -> Result<(), CompileError> {
+    let command = T::generate_link_command(project, compiler_options, objects, output);
+    let mut inputs = objects.to_vec();
+    inputs.extend(compiler_options.dependencies.built_libraries.iter().cloned());
+    if !cache::is_up_to_date(&output, &command, &inputs) {
+        if compiler_options.verbose {
+            eprintln!("{}", command.display());
+        }
+
+        // `ar` adds to an existing archive, which would keep objects of deleted sources around
+        if project.package.target == Target::Static && output.exists() {
+            fs::remove_file(output).unwrap();
+        }
+
+        if !command.status().expect("Failed to query linker.").success() {
+            return Err(CompileError {
+                error_type: CompileErrorType::LinkerReturnedNonZero,
+                msg: "Linking terminated due to previous error(s).".to_string(),
+            });
+        }
+
+        cache::record_command(output, &command).unwrap();
     }
 
     Ok(())
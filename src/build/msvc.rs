@@ -0,0 +1,354 @@
+use std::path::{Path, PathBuf};
+use command::CommandLine;
+use project::{Project, Target, Warnings};
+use super::{cache, CompilerOptions, CompilerTrait, Language};
+use super::standard::Standard;
+use super::toolchain::Toolchain;
+
+pub struct MSVC;
+
+/// The `/std:` switch for a standard, for the few standards MSVC has one for.
+fn standard_switch(standard: &Standard) -> Option<&'static str> {
+    match standard.name.as_str() {
+        "c11" => Some("/std:c11"),
+        "c17" | "c18" => Some("/std:c17"),
+        "c++14" => Some("/std:c++14"),
+        "c++17" => Some("/std:c++17"),
+        "c++2a" | "c++20" => Some("/std:c++20"),
+        "c++2b" | "c++23" => Some("/std:c++latest"),
+        _ => None,
+    }
+}
+
+/// Returns true for the standards MSVC compiles without a switch, as it has no mode for
+/// them: C is compiled as C89/C99 by default, and its default of C++14 covers C++11.
+fn needs_no_switch(standard: &Standard) -> bool {
+    match standard.name.as_str() {
+        "c89" | "c90" | "c99" | "c++11" => true,
+        _ => false,
+    }
+}
+
+impl CompilerTrait for MSVC {
+    fn display() -> String {
+        String::from("MSVC")
+    }
+
+    fn exists() -> bool {
+        CommandLine::new("cl").arg("/?").succeeds()
+    }
+
+    fn toolchain() -> Toolchain {
+        Toolchain {
+            cc: String::from("cl"),
+            cxx: String::from("cl"),
+            ar: String::from("lib"),
+            ld: Some(String::from("link")),
+        }
+    }
+
+    fn supports_standard(_: &Toolchain, standard: &Standard) -> bool {
+        // MSVC has no GNU dialects
+        standard_switch(standard).is_some() || needs_no_switch(standard)
+    }
+
+    fn generate_command(
+        project: &Project,
+        compiler_options: &CompilerOptions,
+        source: &Path,
+        object: &Path,
+    ) -> CommandLine {
+        let language = Language::of(source).unwrap();
+        let mut command = CommandLine::configured(compiler_options.toolchain.compiler(&language));
+        command.arg("/nologo");
+
+        // Compile the source only, leaving linking for later
+        command
+            .arg("/c")
+            .path(source)
+            .arg(format!("/Fo{}", object.display()));
+
+        // cl.exe only knows `.c` and `.cpp`, so we tell it the language of every source
+        match language {
+            Language::Cpp => command.args(vec!["/TP", "/EHsc"]),
+            _ => command.arg("/TC"),
+        };
+
+        // Have cl.exe list the headers this object depends on, so we know when to recompile it
+        command
+            .arg("/sourceDependencies")
+            .path(&cache::depfile_path(object));
+
+        if let Some(switch) = compiler_options.standard(&language).and_then(standard_switch) {
+            command.arg(switch);
+        }
+
+        // Warnings
+        if let Some(ref build) = project.build {
+            match build.warnings {
+                Some(Warnings::All) => {
+                    command.arg("/W3");
+                }
+                Some(Warnings::Extra) => {
+                    command.arg("/W4");
+                }
+                Some(Warnings::None) => {
+                    command.arg("/W0");
+                }
+                Some(Warnings::Default) | None => {}
+            }
+            if build.warnings_as_errors == Some(true) {
+                command.arg("/WX");
+            }
+        }
+
+        // Optimizations and debug information, as the profile asks for. MSVC only
+        // optimizes for either size (/O1) or speed (/O2).
+        let profile = &compiler_options.profile;
+        match profile.opt_level.as_str() {
+            "0" => command.arg("/Od"),
+            "s" | "z" => command.arg("/O1"),
+            _ => command.arg("/O2"),
+        };
+        if profile.debug {
+            // Kept inside of the object, as objects compiled in parallel cannot share one .pdb
+            command.arg("/Z7");
+        }
+        if profile.lto {
+            command.arg("/GL");
+        }
+
+        command
+            .arg(format!("/DMAID_PACKAGE_NAME=\"{}\"", project.package.name))
+            .arg(format!("/DMAID_PACKAGE_VERSION=\"{}\"", project.package.version));
+        for define in &profile.defines {
+            command.arg(format!("/D{}", define));
+        }
+        command.args(&profile.flags);
+
+        // Header search directories
+        for directory in &compiler_options.dependencies.header_search_directories {
+            command.arg("/I").path(directory);
+        }
+
+        command.args(&compiler_options.dependencies.compile_flags);
+
+        if let Some(ref build) = project.build {
+            if let Some(ref options) = build.msvc_options {
+                command.args(options);
+            }
+        }
+
+        command
+    }
+
+    fn generate_link_command(
+        project: &Project,
+        compiler_options: &CompilerOptions,
+        objects: &[PathBuf],
+        output: &Path,
+    ) -> CommandLine {
+        // Static libraries are archived by lib.exe
+        if project.package.target == Target::Static {
+            let mut command = CommandLine::configured(&compiler_options.toolchain.ar);
+            command
+                .arg("/nologo")
+                .arg(format!("/OUT:{}", output.display()));
+            for object in objects {
+                command.path(object);
+            }
+            return command;
+        }
+
+        let mut command = match compiler_options.toolchain.ld {
+            Some(ref ld) => CommandLine::configured(ld),
+            None => CommandLine::new("link"),
+        };
+        command.arg("/nologo");
+
+        // Objects
+        for object in objects {
+            command.path(object);
+        }
+
+        command.arg(format!("/OUT:{}", output.display()));
+
+        if project.package.target == Target::Dynamic {
+            command.arg("/DLL");
+        }
+
+        let profile = &compiler_options.profile;
+        if profile.debug {
+            command.arg("/DEBUG");
+        }
+        if profile.lto {
+            command.arg("/LTCG");
+        }
+
+        // Linker search directories
+        for directory in &compiler_options.dependencies.linker_search_directories {
+            command.arg(format!("/LIBPATH:{}", directory.display()));
+        }
+
+        // Libraries built by maid are named like they are everywhere else, so we give their
+        // paths instead of their names. A DLL is linked through its import library.
+        let dependencies = &compiler_options.dependencies;
+        for library in &dependencies.built_libraries {
+            if library.extension().map_or(false, |ext| ext == "dll") {
+                command.path(&library.with_extension("lib"));
+            } else {
+                command.path(library);
+            }
+        }
+
+        // Every other library is looked up as `<name>.lib`, like `SDL2` as `SDL2.lib`
+        for name in &dependencies.link_libraries {
+            let built = dependencies.built_libraries.iter().any(|library| {
+                let stem = library.file_stem().unwrap().to_string_lossy();
+                stem == name.as_str() || stem == format!("lib{}", name)
+            });
+            if !built {
+                command.arg(format!("{}.lib", name));
+            }
+        }
+
+        command.args(&dependencies.link_flags);
+
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use dependencies::ResolvedDependencies;
+    use super::super::Profile;
+
+    fn project(target: &str) -> Project {
+        ::toml::from_str(&format!(
+            "[package]\nname = \"hello\"\nversion = \"1.2.0\"\nauthors = []\ntarget = \"{}\"\n",
+            target
+        )).unwrap()
+    }
+
+    fn options(profile: &str, standards: &[&str]) -> CompilerOptions {
+        CompilerOptions {
+            profile: Profile::resolve(profile, &BTreeMap::new()).unwrap(),
+            verbose: false,
+            jobs: 1,
+            output_directory: PathBuf::from("target/release"),
+            object_directory: PathBuf::from("target/release/obj/hello"),
+            source_directory: PathBuf::from("source"),
+            sources: vec![PathBuf::from("source/main.c")],
+            binaries: vec![],
+            language: Language::C,
+            standards: standards.iter().map(|name| Standard::parse(name).unwrap()).collect(),
+            toolchain: MSVC::toolchain(),
+            triple: None,
+            sysroot: None,
+            dependencies: ResolvedDependencies {
+                header_search_directories: vec![PathBuf::from("include")],
+                linker_search_directories: vec![PathBuf::from("C:/SDL2/lib")],
+                link_libraries: vec![String::from("SDL2")],
+                ..ResolvedDependencies::default()
+            },
+        }
+    }
+
+    fn compile(project: &Project, options: &CompilerOptions) -> Vec<String> {
+        MSVC::generate_command(
+            project,
+            options,
+            Path::new("source/main.c"),
+            Path::new("target/release/obj/hello/main.c.obj"),
+        ).to_vec()
+    }
+
+    fn link(project: &Project, options: &CompilerOptions, output: &str) -> Vec<String> {
+        let objects = vec![PathBuf::from("target/release/obj/hello/main.c.obj")];
+        MSVC::generate_link_command(project, options, &objects, Path::new(output)).to_vec()
+    }
+
+    fn has(command: &[String], argument: &str) -> bool {
+        command.iter().any(|a| a == argument)
+    }
+
+    #[test]
+    fn compile_command() {
+        let command = compile(&project("Executable"), &options("release", &[]));
+        assert_eq!(command[0], "cl");
+        assert!(has(&command, "/c"));
+        assert!(has(&command, "/Fotarget/release/obj/hello/main.c.obj"));
+        assert!(has(&command, "/TC"));
+        assert!(has(&command, "/O2"));
+        assert!(has(&command, "/DMAID_PACKAGE_NAME=\"hello\""));
+        assert!(has(&command, "/DMAID_PACKAGE_VERSION=\"1.2.0\""));
+
+        // The directory is an argument of its own, so it may contain spaces
+        let include = command.iter().position(|a| a == "/I").unwrap();
+        assert_eq!(command[include + 1], "include");
+    }
+
+    #[test]
+    fn debug_compile_command() {
+        let command = compile(&project("Executable"), &options("debug", &[]));
+        assert!(has(&command, "/Od"));
+        assert!(has(&command, "/Z7"));
+        assert!(!has(&command, "/O2"));
+    }
+
+    #[test]
+    fn standard_switches() {
+        let command = compile(&project("Executable"), &options("debug", &["c11"]));
+        assert!(has(&command, "/std:c11"));
+
+        let toolchain = MSVC::toolchain();
+        let supported = |name: &str| MSVC::supports_standard(&toolchain, &Standard::parse(name).unwrap());
+        assert!(supported("c17"));
+        assert!(supported("c++20"));
+        assert!(supported("c99"));
+        assert!(supported("c++11"));
+        assert!(!supported("c++98"));
+        // MSVC has no GNU dialects
+        assert!(!supported("gnu11"));
+        assert!(!supported("gnu++17"));
+        assert_eq!(standard_switch(&Standard::parse("c++23").unwrap()), Some("/std:c++latest"));
+
+        // C99 is what cl.exe compiles anyway
+        let command = compile(&project("Executable"), &options("debug", &["c99"]));
+        assert!(!command.iter().any(|argument| argument.starts_with("/std:")));
+    }
+
+    #[test]
+    fn link_command() {
+        let command = link(&project("Executable"), &options("release", &[]), "target/release/hello.exe");
+        assert_eq!(command[0], "link");
+        assert!(has(&command, "/OUT:target/release/hello.exe"));
+        assert!(has(&command, "/LIBPATH:C:/SDL2/lib"));
+        assert!(has(&command, "SDL2.lib"));
+        assert!(!has(&command, "/DLL"));
+    }
+
+    #[test]
+    fn dynamic_link_command() {
+        let command = link(&project("Dynamic"), &options("release", &[]), "target/release/hello.dll");
+        assert_eq!(command[0], "link");
+        assert!(has(&command, "/DLL"));
+        assert!(has(&command, "/OUT:target/release/hello.dll"));
+    }
+
+    #[test]
+    fn static_link_command() {
+        let command = link(&project("Static"), &options("release", &[]), "target/release/hello.lib");
+        assert_eq!(
+            command,
+            vec![
+                "lib",
+                "/nologo",
+                "/OUT:target/release/hello.lib",
+                "target/release/obj/hello/main.c.obj",
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Build profiles, like `debug`, `release`, or a custom `[profile.asan]` from the project file.
+
+use std::collections::BTreeMap;
+use project::{OptLevel, ProfileConfig};
+use super::{CompileError, CompileErrorType};
+
+/// Everything a profile changes about how a project is compiled.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    /// The name of the profile, which is also the folder in `target/` it is built into.
+    pub name: String,
+    /// `0`, `1`, `2`, `3`, `s`, or `z`, as in `-O3`.
+    pub opt_level: String,
+    /// Whether to generate debug information (`-g`).
+    pub debug: bool,
+    /// Preprocessor defines, like `MAID_DEBUG` or `NDEBUG=1`.
+    pub defines: Vec<String>,
+    /// Whether to use link-time optimization.
+    pub lto: bool,
+    /// Extra flags given to the compiler when both compiling and linking, like `-fsanitize=address`.
+    pub flags: Vec<String>,
+}
+
+impl Profile {
+    /// The profiles every project has, even without a [profile] table.
+    fn builtin(name: &str) -> Option<Profile> {
+        match name {
+            "debug" => Some(Profile {
+                name: String::from("debug"),
+                opt_level: String::from("0"),
+                debug: true,
+                defines: vec![String::from("MAID_DEBUG")],
+                lto: false,
+                flags: vec![],
+            }),
+            "release" => Some(Profile {
+                name: String::from("release"),
+                opt_level: String::from("3"),
+                debug: false,
+                defines: vec![String::from("MAID_RELEASE")],
+                lto: false,
+                flags: vec![],
+            }),
+            _ => None,
+        }
+    }
+
+    /// Finds the profile called `name`. A profile in the project file starts out as a copy
+    /// of the profile it `inherits` (or of `debug`), and replaces whatever it specifies.
+    pub fn resolve(
+        name: &str,
+        configs: &BTreeMap<String, ProfileConfig>,
+    ) -> Result<Profile, CompileError> {
+        Profile::resolve_inner(name, configs, 0)
+    }
+
+    fn resolve_inner(
+        name: &str,
+        configs: &BTreeMap<String, ProfileConfig>,
+        depth: usize,
+    ) -> Result<Profile, CompileError> {
+        // Profiles inheriting from each other would never end
+        if depth > configs.len() {
+            return Err(CompileError {
+                error_type: CompileErrorType::UnknownProfile,
+                msg: format!("Profile `{}` inherits from itself.", name),
+            });
+        }
+
+        let config = match configs.get(name) {
+            Some(config) => config,
+            None => {
+                return match Profile::builtin(name) {
+                    Some(profile) => Ok(profile),
+                    None => Err(CompileError {
+                        error_type: CompileErrorType::UnknownProfile,
+                        msg: format!("There is no [profile.{}] in the project file.", name),
+                    }),
+                }
+            }
+        };
+
+        let mut profile = match config.inherits {
+            Some(ref base) => Profile::resolve_inner(base, configs, depth + 1)?,
+            // [profile.debug] and [profile.release] change the built-in ones
+            None => match Profile::builtin(name) {
+                Some(profile) => profile,
+                None => Profile::builtin("debug").unwrap(),
+            },
+        };
+
+        profile.name = name.to_owned();
+        if let Some(ref opt_level) = config.opt_level {
+            profile.opt_level = match *opt_level {
+                OptLevel::Number(level) => level.to_string(),
+                OptLevel::Name(ref level) => level.clone(),
+            };
+        }
+        if let Some(debug) = config.debug {
+            profile.debug = debug;
+        }
+        if let Some(ref defines) = config.defines {
+            profile.defines = defines.clone();
+        }
+        if let Some(lto) = config.lto {
+            profile.lto = lto;
+        }
+        if let Some(ref flags) = config.flags {
+            profile.flags = flags.clone();
+        }
+
+        match profile.opt_level.as_str() {
+            "0" | "1" | "2" | "3" | "s" | "z" => Ok(profile),
+            level => Err(CompileError {
+                error_type: CompileErrorType::UnknownProfile,
+                msg: format!(
+                    "Profile `{}` has opt_level `{}`, but it must be 0, 1, 2, 3, \"s\", or \"z\".",
+                    name, level
+                ),
+            }),
+        }
+    }
+
+    /// Describes the profile for the "Finished" message, like `[optimized + debuginfo]`.
+    pub fn description(&self) -> String {
+        let optimized = if self.opt_level == "0" {
+            "unoptimized"
+        } else {
+            "optimized"
+        };
+
+        if self.debug {
+            format!("[{} + debuginfo]", optimized)
+        } else {
+            format!("[{}]", optimized)
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! Builds the standalone programs of a project, like every test in `tests/`, every
+//! benchmark in `benches/`, and every example in `examples/`.
+//!
+//! Each file in the directory with a `main` function becomes its own executable, linked
+//! against the project itself: against its library, or against every object of an
+//! executable project besides the `main` of each of its executables. Files without a
+//! `main` are helpers, which are linked into every program of the directory.
+//!
+//! Tests may also be written as `MAID_TEST(name)` cases from `maid_test.h`, anywhere in
+//! `source/` or `tests/`. Those are collected into one more program, whose `main` we
+//! generate, and which runs the cases named on its command line.
+
+use std::fs::{DirBuilder, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use dependencies::ResolvedDependencies;
+use project::{Project, Target, Workspace};
+use utils;
+use super::{collect_sources, compile_objects, configure, database_path, finish, link, link_outputs};
+use super::{clang, gcc, msvc, BuildOptions, Built, CompileError, Compiler, CompilerOptions,
+            CompilerTrait, Database, Language, Os};
+
+/// The test framework header, which every project can include.
+const TEST_HEADER: &str = include_str!("maid_test.h");
+
+/// The `main` of the program running the `MAID_TEST` cases, which follows the table of
+/// cases. With no arguments, it runs every case.
+const HARNESS_MAIN: &str = r#"
+int main(int argc, char *argv[])
+{
+    int i, j;
+
+    if (argc < 2) {
+        for (i = 0; i < maid_test_count; i++) {
+            maid_tests[i].run();
+        }
+        return 0;
+    }
+
+    for (j = 1; j < argc; j++) {
+        for (i = 0; i < maid_test_count; i++) {
+            if (strcmp(argv[j], maid_tests[i].name) == 0) {
+                maid_tests[i].run();
+                break;
+            }
+        }
+        if (i == maid_test_count) {
+            fprintf(stderr, "There is no test named `%s`.\n", argv[j]);
+            return 2;
+        }
+    }
+
+    return 0;
+}
+"#;
+
+/// The kinds of programs a project can have besides itself.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Kind {
+    Tests,
+    Benches,
+    Examples,
+}
+
+impl Kind {
+    /// The directory of the project the programs are in, which is also where they are
+    /// built inside of the output directory.
+    pub fn directory(&self) -> &'static str {
+        match *self {
+            Kind::Tests => "tests",
+            Kind::Benches => "benches",
+            Kind::Examples => "examples",
+        }
+    }
+}
+
+/// A program built from a file in `tests/`, `benches/`, or `examples/`.
+#[derive(Clone, Debug)]
+pub struct Program {
+    /// The path of its source file inside of the directory, without the extension,
+    /// like `parser` for `tests/parser.c`.
+    pub name: String,
+    /// The package it belongs to.
+    pub package: String,
+    pub path: PathBuf,
+    /// The root of its project, which it is run from.
+    pub root: PathBuf,
+    /// Where the dynamic libraries it links against are.
+    pub library_directories: Vec<PathBuf>,
+    /// The `MAID_TEST` cases it runs, given the name of one as its argument. Empty for
+    /// every program but the generated one.
+    pub cases: Vec<String>,
+}
+
+/// Reads a source file, or nothing when it cannot be read.
+fn read_source(source: &Path) -> String {
+    let mut contents = String::new();
+    if let Ok(mut file) = File::open(source) {
+        if file.read_to_string(&mut contents).is_err() {
+            contents.clear();
+        }
+    }
+    contents
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns true when a source file defines a `main` function.
+fn has_main(source: &Path) -> bool {
+    let contents = read_source(source);
+    contents.match_indices("main").any(|(index, _)| {
+        let before = contents[..index].chars().next_back();
+        let after = contents[index + 4..].trim_start().chars().next();
+        !before.is_some_and(is_identifier) && after == Some('(')
+    })
+}
+
+/// Finds the name of every `MAID_TEST(name)` case in a source file.
+fn test_cases(source: &Path) -> Vec<String> {
+    let contents = read_source(source);
+    let mut cases = Vec::<String>::new();
+
+    for (index, marker) in contents.match_indices("MAID_TEST(") {
+        if contents[..index].chars().next_back().is_some_and(is_identifier) {
+            continue;
+        }
+
+        let rest = &contents[index + marker.len()..];
+        let name = match rest.find(')') {
+            Some(end) => rest[..end].trim().to_owned(),
+            None => continue,
+        };
+        // The name becomes part of a function's name, so anything else is not a case
+        if !name.is_empty() && name.chars().all(is_identifier) && !cases.contains(&name) {
+            cases.push(name);
+        }
+    }
+
+    cases
+}
+
+/// Returns true when a source file includes `maid_test.h`.
+pub fn uses_test_header(source: &Path) -> bool {
+    read_source(source).contains("maid_test.h")
+}
+
+/// Writes `maid_test.h` into the target directory, and adds the directory to include it
+/// from to `dependencies`.
+pub fn add_test_header(dependencies: &mut ResolvedDependencies, options: &BuildOptions) {
+    let directory = options.target_directory.join("maid").join("include");
+    let mut dir_builder = DirBuilder::new();
+    dir_builder.recursive(true);
+    dir_builder.create(&directory).unwrap();
+    utils::write_if_changed(&directory.join("maid_test.h"), TEST_HEADER).unwrap();
+
+    if !dependencies.header_search_directories.contains(&directory) {
+        dependencies.header_search_directories.push(directory);
+    }
+}
+
+/// Generates the source of the program running the `MAID_TEST` cases.
+fn generate_harness(cases: &[String]) -> String {
+    let mut harness = String::from(
+        "/* Generated by maid, to run the MAID_TEST cases named on the command line. */\n\n",
+    );
+    harness.push_str("#include <stdio.h>\n#include <string.h>\n\n");
+
+    for case in cases {
+        harness.push_str(&format!("void maid_test_{}(void);\n", case));
+    }
+
+    harness.push_str("\nstatic const struct {\n    const char *name;\n    void (*run)(void);\n} maid_tests[] = {\n");
+    for case in cases {
+        harness.push_str(&format!("    {{\"{}\", maid_test_{}}},\n", case, case));
+    }
+    harness.push_str("};\n\n");
+    harness.push_str(&format!("static const int maid_test_count = {};\n", cases.len()));
+
+    harness.push_str(HARNESS_MAIN);
+    harness
+}
+
+/// Builds every project of the workspace, along with its programs of `kind`.
+pub fn build(
+    workspace: &Workspace,
+    options: &BuildOptions,
+    kind: Kind,
+) -> Result<Vec<Program>, CompileError> {
+    let mut database = Database::open(&database_path(workspace));
+    let mut built = Built::new();
+    let mut programs = Vec::<Program>::new();
+
+    for project in &workspace.members {
+        let root = project.root.canonicalize().unwrap_or_else(|_| project.root.clone());
+        let mut parents = vec![root.clone()];
+
+        let (compiler, compiler_options) =
+            configure(project, options, &mut parents, &mut built, &mut database)?;
+        // Members depending on this one link against what is built here
+        built.insert(root, compiler_options.dependencies.clone());
+        programs.extend(match compiler {
+            Compiler::GNU => {
+                build_programs::<gcc::GCC>(project, options, compiler_options, kind, &mut database)?
+            }
+            Compiler::Clang => {
+                build_programs::<clang::Clang>(project, options, compiler_options, kind, &mut database)?
+            }
+            Compiler::MSVC => {
+                build_programs::<msvc::MSVC>(project, options, compiler_options, kind, &mut database)?
+            }
+        });
+    }
+
+    finish(workspace, options, &database);
+    Ok(programs)
+}
+
+/// The path of a program's executable.
+fn program_path(compiler_options: &CompilerOptions, path: PathBuf) -> PathBuf {
+    let mut path = path;
+    if compiler_options.os() == Os::Windows {
+        path.set_extension("exe");
+    }
+
+    let mut dir_builder = DirBuilder::new();
+    dir_builder.recursive(true);
+    dir_builder.create(path.parent().unwrap()).unwrap();
+    path
+}
+
+/// Builds a project, and then all of its programs of `kind`.
+fn build_programs<T: CompilerTrait>(
+    project: &Project,
+    options: &BuildOptions,
+    compiler_options: CompilerOptions,
+    kind: Kind,
+    database: &mut Database,
+) -> Result<Vec<Program>, CompileError> {
+    // The project itself comes first, as the programs are linked against it
+    let objects = compile_objects::<T>(project, &compiler_options, database)?;
+    link_outputs::<T>(project, &compiler_options, &objects, None)?;
+    let output = compiler_options
+        .output_directory
+        .join(project.output_name(compiler_options.os()));
+
+    let source_directory = project.root.join(kind.directory());
+    let sources = if source_directory.is_dir() {
+        collect_sources(&source_directory)
+    } else {
+        vec![]
+    };
+
+    // The MAID_TEST cases, from both the project and the helpers of its tests
+    let mut cases = Vec::<String>::new();
+    if kind == Kind::Tests {
+        for source in &compiler_options.sources {
+            if !compiler_options.is_binary(source) {
+                cases.extend(test_cases(source));
+            }
+        }
+        for source in &sources {
+            if !has_main(source) {
+                cases.extend(test_cases(source));
+            }
+        }
+    }
+
+    if sources.is_empty() && cases.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let program_directory = compiler_options
+        .output_directory
+        .join(kind.directory())
+        .join(&project.package.name);
+
+    // Programs are executables, whatever the project builds
+    let mut program_project = project.clone();
+    program_project.package.target = Target::Executable;
+
+    let mut program_options = compiler_options.clone();
+    program_options.object_directory = program_directory.join("obj");
+    program_options.source_directory = source_directory.clone();
+    program_options.sources = sources.clone();
+    if compiler_options
+        .sources
+        .iter()
+        .chain(&sources)
+        .any(|source| Language::of(source) == Some(Language::Cpp))
+    {
+        program_options.language = Language::Cpp;
+    }
+    if kind == Kind::Tests {
+        // Makes the MAID_TEST cases visible to the generated main
+        program_options.profile.defines.push(String::from("MAID_TESTING"));
+    }
+    if sources.iter().any(|source| uses_test_header(source)) {
+        add_test_header(&mut program_options.dependencies, options);
+    }
+
+    // What every program is linked against, besides its own objects
+    let mut library_options = program_options.clone();
+    let linked = if project.package.target == Target::Executable {
+        // The program brings its own main, instead of those of the executables
+        compiler_options.common_objects(&objects)
+    } else {
+        // The library has to come before the libraries it depends on
+        let mut dependencies = ResolvedDependencies {
+            linker_search_directories: vec![compiler_options.output_directory.clone()],
+            link_libraries: vec![project.package.name.clone()],
+            built_libraries: vec![output.clone()],
+            ..ResolvedDependencies::default()
+        };
+        dependencies.extend(library_options.dependencies);
+        library_options.dependencies = dependencies;
+        vec![]
+    };
+
+    let program_objects = compile_objects::<T>(&program_project, &program_options, database)?;
+
+    // Helpers are linked into every program
+    let mut helpers = Vec::<PathBuf>::new();
+    let mut mains = Vec::<(&PathBuf, &PathBuf)>::new();
+    for (source, object) in sources.iter().zip(&program_objects) {
+        if has_main(source) {
+            mains.push((source, object));
+        } else {
+            helpers.push(object.clone());
+        }
+    }
+
+    let mut programs = Vec::<Program>::new();
+    for (source, object) in mains {
+        let name = source
+            .strip_prefix(&source_directory)
+            .unwrap_or(source)
+            .with_extension("")
+            .to_string_lossy()
+            .replace('\\', "/");
+        let path = program_path(&program_options, program_directory.join(&name));
+
+        let mut objects = vec![object.clone()];
+        objects.extend(helpers.iter().cloned());
+        objects.extend(linked.iter().cloned());
+        link::<T>(&program_project, &library_options, &objects, &path)?;
+
+        programs.push(Program {
+            name: name,
+            package: project.package.name.clone(),
+            path: path,
+            root: project.root.clone(),
+            library_directories: compiler_options.library_directories(),
+            cases: vec![],
+        });
+    }
+
+    if !cases.is_empty() {
+        // The project's own cases are only visible when its sources are compiled for
+        // testing, so they are compiled once more, and linked instead of the project
+        let harness_directory = program_directory.join("harness");
+        let mut harness_options = program_options.clone();
+        harness_options.object_directory = harness_directory.join("obj");
+        harness_options.source_directory = compiler_options.source_directory.clone();
+        harness_options.sources = compiler_options
+            .sources
+            .iter()
+            .filter(|source| !compiler_options.is_binary(source))
+            .cloned()
+            .collect();
+        let mut objects = compile_objects::<T>(&program_project, &harness_options, database)?;
+
+        // The generated main goes beside the objects, where it is compiled like any source
+        let harness_source = harness_directory.join("main.c");
+        let mut dir_builder = DirBuilder::new();
+        dir_builder.recursive(true);
+        dir_builder.create(&harness_directory).unwrap();
+        utils::write_if_changed(&harness_source, &generate_harness(&cases)).unwrap();
+
+        harness_options.source_directory = harness_directory.clone();
+        harness_options.sources = vec![harness_source];
+        objects.extend(compile_objects::<T>(&program_project, &harness_options, database)?);
+        objects.extend(helpers.iter().cloned());
+
+        let path = program_path(&harness_options, harness_directory.join("harness"));
+        link::<T>(&program_project, &harness_options, &objects, &path)?;
+
+        programs.push(Program {
+            name: String::from("harness"),
+            package: project.package.name.clone(),
+            path: path,
+            root: project.root.clone(),
+            library_directories: compiler_options.library_directories(),
+            cases: cases,
+        });
+    }
+
+    Ok(programs)
+}
@@ -0,0 +1,98 @@
+//! Language standards, like `standard = "c11"` or `standard = "gnu++17"` in [package].
+
+use super::{CompileError, CompileErrorType, Language};
+
+/// The ISO C standards, as spelled after `-std=c` or `-std=gnu`.
+const C_STANDARDS: &[&str] = &["89", "90", "99", "11", "17", "18", "2x", "23"];
+/// The ISO C++ standards, as spelled after `-std=c++` or `-std=gnu++`.
+const CPP_STANDARDS: &[&str] = &["98", "03", "11", "14", "17", "2a", "20", "2b", "23"];
+
+/// A language standard the sources of a project are written in.
+#[derive(Clone, Debug)]
+pub struct Standard {
+    /// The name as written in the project file, which is also what `-std=` takes.
+    pub name: String,
+    pub language: Language,
+}
+
+impl Standard {
+    /// Reads a standard like `c99`, `gnu11`, `c++20` or `gnu++14`, or returns `None`
+    /// when it is not one we know of.
+    pub fn parse(name: &str) -> Option<Standard> {
+        // The C++ prefixes must come first, since `c++20` also starts with `c`
+        let (language, version) = if let Some(version) = name.strip_prefix("c++") {
+            (Language::Cpp, version)
+        } else if let Some(version) = name.strip_prefix("gnu++") {
+            (Language::Cpp, version)
+        } else if let Some(version) = name.strip_prefix("gnu") {
+            (Language::C, version)
+        } else if let Some(version) = name.strip_prefix('c') {
+            (Language::C, version)
+        } else {
+            return None;
+        };
+
+        let known = if language == Language::Cpp {
+            CPP_STANDARDS
+        } else {
+            C_STANDARDS
+        };
+        if known.contains(&version) {
+            Some(Standard {
+                name: name.to_owned(),
+                language: language,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Checks the standards of a project against the languages its sources are written in.
+    /// There may be at most one standard for each language.
+    pub fn validate(names: &[String], languages: &[Language]) -> Result<Vec<Standard>, CompileError> {
+        let mut standards = Vec::<Standard>::new();
+
+        for name in names {
+            let standard = match Standard::parse(name) {
+                Some(standard) => standard,
+                None => {
+                    return Err(CompileError {
+                        error_type: CompileErrorType::UnknownStandard,
+                        msg: format!(
+                            "`{}` is not a language standard. Try one like \"c11\", \"gnu99\", \"c++17\", or \"gnu++20\".",
+                            name
+                        ),
+                    })
+                }
+            };
+
+            if !languages.contains(&standard.language) {
+                return Err(CompileError {
+                    error_type: CompileErrorType::UnknownStandard,
+                    msg: format!(
+                        "`{}` is a {} standard, but this project has no {} sources.",
+                        name,
+                        standard.language.display(),
+                        standard.language.display()
+                    ),
+                });
+            }
+
+            if let Some(other) = standards.iter().find(|s| s.language == standard.language) {
+                return Err(CompileError {
+                    error_type: CompileErrorType::UnknownStandard,
+                    msg: format!(
+                        "Both `{}` and `{}` are {} standards, but only one may be used.",
+                        other.name,
+                        name,
+                        standard.language.display()
+                    ),
+                });
+            }
+
+            standards.push(standard);
+        }
+
+        Ok(standards)
+    }
+}
@@ -0,0 +1,217 @@
+//! The programs a project is compiled, archived, and linked with.
+//!
+//! Every backend has its usual programs (`gcc`, `g++`, `ar`), but any of them may be
+//! replaced, like with a versioned `gcc-12` or a wrapper like `ccache gcc`. In order of
+//! priority, they are taken from:
+//! 1. The `CC`, `CXX`, `AR`, and `LD` environment variables. GCC and Clang still link
+//!    through the compiler driver, which is told to use `LD` with `-fuse-ld=`, unless it
+//!    is plain `ld`, which they use anyway.
+//! 2. The [toolchain] of the project file.
+//! 3. The [toolchain] of the user's Config.toml.
+//!
+//! Those all build for this machine, so none of them are used when cross compiling with
+//! `--target`. The programs are then taken from:
+//! 1. The environment variables for the triple, like `CC_aarch64_linux_gnu` or
+//!    `LD_aarch64_linux_gnu`.
+//! 2. The [target.<triple>] of the project file.
+//!
+//! Anything not configured is the backend's usual program, which for GCC is prefixed for
+//! the triple, like `aarch64-linux-gnu-gcc`.
+
+use std::env;
+use std::path::Path;
+use command::CommandLine;
+use project::{Project, TargetConfig, ToolchainConfig};
+use super::{CompileError, CompileErrorType, Compiler, Language, Triple};
+
+/// The programs a backend runs, after applying the configuration.
+#[derive(Clone, Debug)]
+pub struct Toolchain {
+    /// The C compiler (and for GCC and Clang, also the assembler).
+    pub cc: String,
+    /// The C++ compiler.
+    pub cxx: String,
+    /// The program static libraries are archived with.
+    pub ar: String,
+    /// The program executables and dynamic libraries are linked with. MSVC runs it directly,
+    /// while GCC and Clang always link with `cc` (or `cxx` when there are C++ objects),
+    /// which is told to use it with `-fuse-ld=`.
+    pub ld: Option<String>,
+}
+
+impl Toolchain {
+    /// Replaces the programs of `defaults` with those that are configured.
+    pub fn resolve(configured: &ToolchainConfig, defaults: Toolchain) -> Toolchain {
+        Toolchain {
+            cc: configured.cc.clone().unwrap_or(defaults.cc),
+            cxx: configured.cxx.clone().unwrap_or(defaults.cxx),
+            ar: configured.ar.clone().unwrap_or(defaults.ar),
+            ld: configured.ld.clone().or(defaults.ld),
+        }
+    }
+
+    /// Puts `prefix` in front of the compilers and the archiver, like `aarch64-linux-gnu-`.
+    pub fn with_prefix(self, prefix: &str) -> Toolchain {
+        Toolchain {
+            cc: format!("{}{}", prefix, self.cc),
+            cxx: format!("{}{}", prefix, self.cxx),
+            ar: format!("{}{}", prefix, self.ar),
+            ld: self.ld.map(|ld| format!("{}{}", prefix, ld)),
+        }
+    }
+
+    /// The switch telling GCC or Clang to link with the configured linker, like
+    /// `-fuse-ld=lld` for `ld = "ld.lld"`. There is none for plain `ld`, which they use anyway.
+    pub fn fuse_ld(&self) -> Option<String> {
+        let ld = self.ld.as_ref()?.trim();
+        let name = Path::new(ld).file_name().and_then(|name| name.to_str()).unwrap_or(ld);
+
+        // `ld.lld` and `aarch64-linux-gnu-ld.gold` are known to the driver as `lld` and `gold`
+        let name = match name.rfind("ld.") {
+            Some(index) if index == 0 || name[..index].ends_with('-') => &name[index + 3..],
+            _ => name,
+        };
+
+        if name == "ld" || name.ends_with("-ld") {
+            None
+        } else {
+            Some(format!("-fuse-ld={}", name))
+        }
+    }
+
+    /// The compiler for sources of `language`.
+    pub fn compiler(&self, language: &Language) -> &str {
+        match *language {
+            Language::Cpp => &self.cxx,
+            _ => &self.cc,
+        }
+    }
+}
+
+/// Reads a program from an environment variable, like `CC`.
+fn variable(name: &str) -> Option<String> {
+    match env::var(name) {
+        Ok(ref value) if !value.trim().is_empty() => Some(value.trim().to_owned()),
+        _ => None,
+    }
+}
+
+/// Reads a program for a triple from an environment variable, like `CC_aarch64_linux_gnu`
+/// or `CC_aarch64-linux-gnu`.
+fn target_variable(name: &str, triple: &Triple) -> Option<String> {
+    variable(&format!("{}_{}", name, triple.name))
+        .or_else(|| variable(&format!("{}_{}", name, triple.name.replace('-', "_"))))
+}
+
+/// Collects every program configured for `project`, without any defaults. With a `triple`,
+/// only the programs configured for it are, from the environment and its `target` table.
+pub fn configured(
+    project: &Project,
+    triple: Option<&Triple>,
+    target: Option<&TargetConfig>,
+    user: Option<&ToolchainConfig>,
+) -> ToolchainConfig {
+    let environment = match triple {
+        Some(triple) => ToolchainConfig {
+            cc: target_variable("CC", triple),
+            cxx: target_variable("CXX", triple),
+            ar: target_variable("AR", triple),
+            ld: target_variable("LD", triple),
+        },
+        None => ToolchainConfig {
+            cc: variable("CC"),
+            cxx: variable("CXX"),
+            ar: variable("AR"),
+            ld: variable("LD"),
+        },
+    };
+    let target = target.map(|target| ToolchainConfig {
+        cc: target.cc.clone(),
+        cxx: target.cxx.clone(),
+        ar: target.ar.clone(),
+        ld: target.ld.clone(),
+    });
+
+    // From the highest priority to the lowest
+    let layers: Vec<&ToolchainConfig> = match triple {
+        Some(_) => vec![Some(&environment), target.as_ref()],
+        None => vec![Some(&environment), project.toolchain.as_ref(), user],
+    }.into_iter()
+        .filter_map(|layer| layer)
+        .collect();
+    let pick = |field: fn(&ToolchainConfig) -> &Option<String>| {
+        layers.iter().filter_map(|layer| field(layer).clone()).next()
+    };
+
+    ToolchainConfig {
+        cc: pick(|t| &t.cc),
+        cxx: pick(|t| &t.cxx),
+        ar: pick(|t| &t.ar),
+        ld: pick(|t| &t.ld),
+    }
+}
+
+/// Finds out which backend a C compiler is compatible with, like Clang for `clang-17`.
+pub fn detect_compiler(cc: &str) -> Result<Compiler, CompileError> {
+    // cl.exe and clang-cl have no --version, but are easily recognized by name. The
+    // compiler comes after any wrapper, like in `sccache cl`.
+    let command = CommandLine::configured(cc);
+    let words = command.to_vec();
+    let program = words.last().map(|word| word.as_str()).unwrap_or(cc);
+    match Path::new(program).file_stem().and_then(|stem| stem.to_str()) {
+        Some("cl") | Some("clang-cl") => return Ok(Compiler::MSVC),
+        _ => {}
+    }
+
+    let output = match command.clone().arg("--version").output() {
+        Ok(ref output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        _ => {
+            return Err(CompileError {
+                error_type: CompileErrorType::UnknownToolchain,
+                msg: format!("Failed to execute `{} --version`.", cc),
+            })
+        }
+    };
+
+    // Apple's GCC is actually Clang, so Clang has to be checked first
+    if output.contains("clang") {
+        Ok(Compiler::Clang)
+    } else if output.contains("Free Software Foundation") || output.contains("gcc") {
+        Ok(Compiler::GNU)
+    } else {
+        Err(CompileError {
+            error_type: CompileErrorType::UnknownToolchain,
+            msg: format!(
+                "`{}` is neither GCC nor Clang compatible. Set `preferred_compiler` in [build] to choose one.",
+                cc
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fuse_ld(ld: Option<&str>) -> Option<String> {
+        Toolchain {
+            cc: String::from("gcc"),
+            cxx: String::from("g++"),
+            ar: String::from("ar"),
+            ld: ld.map(String::from),
+        }.fuse_ld()
+    }
+
+    #[test]
+    fn linkers_are_chosen_through_the_driver() {
+        assert_eq!(fuse_ld(None), None);
+        assert_eq!(fuse_ld(Some("ld")), None);
+        assert_eq!(fuse_ld(Some("/usr/bin/aarch64-linux-gnu-ld")), None);
+        assert_eq!(fuse_ld(Some("lld")), Some(String::from("-fuse-ld=lld")));
+        assert_eq!(fuse_ld(Some("ld.lld")), Some(String::from("-fuse-ld=lld")));
+        assert_eq!(fuse_ld(Some("/usr/bin/ld.mold")), Some(String::from("-fuse-ld=mold")));
+        assert_eq!(fuse_ld(Some("x86_64-linux-gnu-ld.gold")), Some(String::from("-fuse-ld=gold")));
+    }
+}
@@ -0,0 +1,71 @@
+//! Target triples, like `aarch64-linux-gnu`, which name the platform we are building for
+//! when it is not the one we are running on.
+
+use std::env;
+use super::{CompileError, CompileErrorType};
+
+/// The operating systems whose binaries are named differently.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Os {
+    Windows,
+    MacOS,
+    /// Linux, the BSDs, and everything else naming libraries like `libfoo.so`.
+    Unix,
+}
+
+impl Os {
+    /// The operating system maid is running on.
+    pub fn host() -> Os {
+        match env::consts::OS {
+            "windows" => Os::Windows,
+            "macos" => Os::MacOS,
+            _ => Os::Unix,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Triple {
+    /// The whole triple, as given to `--target`.
+    pub name: String,
+    /// The first part of the triple, like `aarch64` or `i686`.
+    pub arch: String,
+    pub os: Os,
+}
+
+impl Triple {
+    /// Reads a triple like `x86_64-w64-mingw32`, made of an architecture followed by a vendor,
+    /// an operating system, and/or an environment.
+    pub fn parse(name: &str) -> Result<Triple, CompileError> {
+        let parts: Vec<&str> = name.split('-').collect();
+        if parts.len() < 2 || parts.iter().any(|part| part.is_empty()) {
+            return Err(CompileError {
+                error_type: CompileErrorType::UnknownTarget,
+                msg: format!(
+                    "`{}` is not a target triple. Try one like `aarch64-linux-gnu`.",
+                    name
+                ),
+            });
+        }
+
+        let os = if parts[1..]
+            .iter()
+            .any(|&part| part == "windows" || part.starts_with("mingw") || part == "w64")
+        {
+            Os::Windows
+        } else if parts[1..]
+            .iter()
+            .any(|&part| part == "apple" || part.starts_with("darwin") || part.starts_with("macos"))
+        {
+            Os::MacOS
+        } else {
+            Os::Unix
+        };
+
+        Ok(Triple {
+            name: name.to_owned(),
+            arch: parts[0].to_owned(),
+            os: os,
+        })
+    }
+}
@@ -0,0 +1,165 @@
+//! Commands made of a program and a list of arguments, run without a shell in between.
+//!
+//! Paths with spaces and arguments with quotes, like `-DMAID_PACKAGE_NAME="app"`, reach
+//! the program exactly as they were written. A command is only ever turned into a single
+//! string to be shown, like with `--verbose`, or to be recorded, so we notice when it
+//! changes.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Output, Stdio};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CommandLine {
+    pub program: String,
+    pub arguments: Vec<String>,
+}
+
+impl CommandLine {
+    /// Starts a command running `program`, exactly as it is written.
+    pub fn new(program: &str) -> CommandLine {
+        CommandLine {
+            program: program.to_owned(),
+            arguments: vec![],
+        }
+    }
+
+    /// Starts a command with a program from the configuration, like `CC`, which may start
+    /// with a wrapper like `ccache gcc` or end with flags like `gcc -m32`. The words are
+    /// split apart, except for a path to a program with spaces in it, like
+    /// `/opt/My Tools/gcc`, which stays whole.
+    pub fn configured(program: &str) -> CommandLine {
+        let mut words = Vec::<String>::new();
+        let mut rest = program.trim();
+        while !rest.is_empty() {
+            if rest.contains(char::is_whitespace) && Path::new(rest).is_file() {
+                words.push(rest.to_owned());
+                break;
+            }
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            words.push(rest[..end].to_owned());
+            rest = rest[end..].trim_start();
+        }
+
+        let mut words = words.into_iter();
+        CommandLine {
+            program: words.next().unwrap_or_default(),
+            arguments: words.collect(),
+        }
+    }
+
+    pub fn arg<S: Into<String>>(&mut self, argument: S) -> &mut CommandLine {
+        self.arguments.push(argument.into());
+        self
+    }
+
+    pub fn args<I, S>(&mut self, arguments: I) -> &mut CommandLine
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.arguments.extend(arguments.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds a path as an argument of its own.
+    pub fn path(&mut self, path: &Path) -> &mut CommandLine {
+        self.arg(path.to_string_lossy().into_owned())
+    }
+
+    /// The program followed by every argument, like the `arguments` of compile_commands.json.
+    pub fn to_vec(&self) -> Vec<String> {
+        let mut words = vec![self.program.clone()];
+        words.extend(self.arguments.iter().cloned());
+        words
+    }
+
+    /// Writes the command the way it would be typed into a shell, quoting the arguments
+    /// that need it.
+    pub fn display(&self) -> String {
+        self.to_vec()
+            .iter()
+            .map(|word| quote(word))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.arguments);
+        command
+    }
+
+    /// Runs the command and waits for it to finish, capturing everything it prints.
+    pub fn output(&self) -> io::Result<Output> {
+        self.command().output()
+    }
+
+    /// Runs the command and waits for it to finish, printing to our terminal.
+    pub fn status(&self) -> io::Result<ExitStatus> {
+        self.command().status()
+    }
+
+    /// Returns true when the command could be run, and succeeded, without printing anything.
+    pub fn succeeds(&self) -> bool {
+        match self.command()
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+        {
+            Ok(status) => status.success(),
+            // A program that cannot be spawned at all does not exist
+            Err(_) => false,
+        }
+    }
+}
+
+/// Quotes an argument for display, so that it could be pasted back into a shell.
+pub fn quote(argument: &str) -> String {
+    let plain = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./\\=:,+@%".contains(c));
+    if plain {
+        argument.to_owned()
+    } else if cfg!(target_os = "windows") {
+        format!("\"{}\"", argument.replace('"', "\\\""))
+    } else {
+        // Nothing is special inside of single quotes, except for a single quote itself
+        format!("'{}'", argument.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+
+    #[test]
+    fn configured_programs_split_off_wrappers_and_flags() {
+        assert_eq!(CommandLine::configured("gcc").to_vec(), vec!["gcc"]);
+        assert_eq!(CommandLine::configured(" ccache gcc ").to_vec(), vec!["ccache", "gcc"]);
+        assert_eq!(CommandLine::configured("gcc -m32").to_vec(), vec!["gcc", "-m32"]);
+        assert_eq!(CommandLine::new("ccache gcc").to_vec(), vec!["ccache gcc"]);
+    }
+
+    #[test]
+    fn configured_paths_keep_their_spaces() {
+        let directory = env::temp_dir().join(format!("maid-command-{}", ::std::process::id()));
+        let tools = directory.join("My Tools");
+        fs::create_dir_all(&tools).unwrap();
+        let gcc = tools.join("gcc");
+        File::create(&gcc).unwrap();
+        let gcc = gcc.to_string_lossy().into_owned();
+
+        assert_eq!(CommandLine::configured(&gcc).to_vec(), vec![gcc.clone()]);
+        assert_eq!(
+            CommandLine::configured(&format!("ccache {}", gcc)).to_vec(),
+            vec![String::from("ccache"), gcc.clone()]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1 +0,0 @@
-//! In the future, this file will be used for online dependency downloading.
\ No newline at end of file
@@ -0,0 +1,251 @@
+//! Git dependencies, like `json = { git = "https://example.com/json.git", tag = "v1.2" }`.
+//!
+//! Every repository is cloned once into `~/.maid/git/db/` (or `$MAID_HOME/git/db/`),
+//! and every commit we build from is checked out into its own folder in
+//! `~/.maid/git/checkouts/`. The exact commit of each dependency is written into the
+//! project's `Maid.lock`, so the next build uses the same code until that entry changes.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use build::{CompileError, CompileErrorType};
+use command::CommandLine;
+use project::{Dependency, Project};
+use utils;
+
+/// The contents of a `Maid.lock` file.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub package: Vec<LockedPackage>,
+}
+
+/// A git dependency pinned to an exact commit.
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct LockedPackage {
+    pub name: String,
+    pub git: String,
+    /// What the project file asked for, like `tag=v1.2`, so we notice when it changes.
+    pub reference: String,
+    pub commit: String,
+}
+
+impl Lockfile {
+    /// Reads the `Maid.lock` of a project, or returns an empty one if there is none.
+    pub fn open(project: &Project) -> Result<Lockfile, CompileError> {
+        let mut contents = String::new();
+        match File::open(project.path("Maid.lock")) {
+            Ok(mut file) => file.read_to_string(&mut contents).unwrap(),
+            Err(_) => return Ok(Lockfile::default()),
+        };
+
+        match ::toml::from_str(&contents) {
+            Ok(lockfile) => Ok(lockfile),
+            Err(e) => Err(CompileError {
+                error_type: CompileErrorType::LockfileCouldNotBeParsed,
+                msg: format!("Maid.lock could not be parsed: {}", e),
+            }),
+        }
+    }
+
+    /// Writes the lockfile into the root of a project.
+    pub fn save(&self, project: &Project) -> Result<(), CompileError> {
+        let path = project.path("Maid.lock");
+        let write = || -> io::Result<()> {
+            let mut file = File::create(&path)?;
+            file.write_all(
+                b"# This file is generated by maid. It pins git dependencies to exact commits.\n\n",
+            )?;
+            file.write_all(::toml::to_string(self).unwrap().as_bytes())?;
+            file.sync_all()
+        };
+
+        match write() {
+            Ok(()) => Ok(()),
+            Err(e) => Err(CompileError {
+                error_type: CompileErrorType::LockfileCouldNotBeWritten,
+                msg: format!("`{}` could not be written: {}", path.display(), e),
+            }),
+        }
+    }
+
+    fn find(&self, name: &str, git: &str, reference: &str) -> Option<&LockedPackage> {
+        self.package
+            .iter()
+            .find(|p| p.name == name && p.git == git && p.reference == reference)
+    }
+}
+
+/// Runs git with the given arguments, returning what it printed.
+fn git(args: &[&str]) -> Result<String, CompileError> {
+    let mut command = CommandLine::new("git");
+    command.args(args.iter().cloned());
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(e) => {
+            return Err(CompileError {
+                error_type: CompileErrorType::GitCommandFailed,
+                msg: format!("Failed to execute `{}`: {}", command.display(), e),
+            })
+        }
+    };
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    } else {
+        Err(CompileError {
+            error_type: CompileErrorType::GitCommandFailed,
+            msg: format!(
+                "`{}` failed: {}",
+                command.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        })
+    }
+}
+
+/// Describes which revision of the repository the project file asks for.
+fn reference(name: &str, dependency: &Dependency) -> Result<String, CompileError> {
+    match (&dependency.rev, &dependency.tag, &dependency.branch) {
+        (&Some(ref rev), &None, &None) => Ok(format!("rev={}", rev)),
+        (&None, &Some(ref tag), &None) => Ok(format!("tag={}", tag)),
+        (&None, &None, &Some(ref branch)) => Ok(format!("branch={}", branch)),
+        (&None, &None, &None) => Ok(String::from("HEAD")),
+        _ => Err(CompileError {
+            error_type: CompileErrorType::DependencyNotFound,
+            msg: format!(
+                "Dependency `{}` may only have one of `rev`, `tag`, or `branch`.",
+                name
+            ),
+        }),
+    }
+}
+
+/// Turns a reference from `reference()` into something `git rev-parse` understands.
+fn revision(reference: &str) -> String {
+    if let Some(rev) = reference.strip_prefix("rev=") {
+        format!("{}^{{commit}}", rev)
+    } else if let Some(tag) = reference.strip_prefix("tag=") {
+        format!("refs/tags/{}^{{commit}}", tag)
+    } else if let Some(branch) = reference.strip_prefix("branch=") {
+        format!("refs/heads/{}", branch)
+    } else {
+        String::from("HEAD")
+    }
+}
+
+/// Hashes a repository's URL the same way with every version of Rust, unlike the
+/// standard library's hasher, as it names a folder every later build has to find again.
+/// This is 64 bit FNV-1a.
+fn url_hash(url: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in url.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Local repositories may be given relative to the project, like `../mathlib.git`.
+/// Those are made absolute, so the same repository always ends up in the same folder.
+fn repository_url(project: &Project, url: &str) -> String {
+    if url.contains("://") || url.contains('@') {
+        url.to_owned()
+    } else {
+        let path = project.path(url);
+        path.canonicalize()
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Makes sure the commit of a git dependency is checked out, and returns where.
+/// The commit is taken from `lockfile` when it has one for this dependency, and recorded
+/// into `updated` either way.
+pub fn checkout(
+    project: &Project,
+    name: &str,
+    dependency: &Dependency,
+    lockfile: &Lockfile,
+    updated: &mut Lockfile,
+) -> Result<PathBuf, CompileError> {
+    let git_url = dependency.git.clone().unwrap();
+    let url = repository_url(project, &git_url);
+    let reference = reference(name, dependency)?;
+
+    // Repositories with the same name but a different URL must not share a folder
+    let folder = format!("{}-{:016x}", name, url_hash(&url));
+    let database = utils::maid_home().join("git").join("db").join(&folder);
+    let database_str = database.to_string_lossy().into_owned();
+    let checkouts = utils::maid_home().join("git").join("checkouts").join(&folder);
+
+    let locked = lockfile.find(name, &git_url, &reference).map(|p| p.commit.clone());
+
+    // We only need to fetch when we do not know the commit yet, or do not have it checked out
+    let commit = match locked {
+        Some(ref commit) if checkouts.join(commit).is_dir() => commit.clone(),
+        _ => {
+            if database.is_dir() {
+                git(&[
+                    "--git-dir",
+                    &database_str,
+                    "fetch",
+                    "--quiet",
+                    "--force",
+                    &url,
+                    "+refs/heads/*:refs/heads/*",
+                    "+refs/tags/*:refs/tags/*",
+                ])?;
+            } else {
+                fs::create_dir_all(database.parent().unwrap()).unwrap();
+                git(&["clone", "--quiet", "--bare", &url, &database_str])?;
+            }
+
+            match locked {
+                Some(commit) => commit,
+                None => git(&[
+                    "--git-dir",
+                    &database_str,
+                    "rev-parse",
+                    "--verify",
+                    &revision(&reference),
+                ])?,
+            }
+        }
+    };
+
+    let checkout = checkouts.join(&commit);
+    if !checkout.is_dir() {
+        let checkout_str = checkout.to_string_lossy().into_owned();
+        fs::create_dir_all(&checkouts).unwrap();
+        git(&["clone", "--quiet", "--no-checkout", &database_str, &checkout_str])?;
+        if let Err(e) = git(&["-C", &checkout_str, "checkout", "--quiet", "--detach", &commit]) {
+            // Do not leave a half checked out folder behind, it would be trusted next time
+            fs::remove_dir_all(&checkout).unwrap();
+            return Err(e);
+        }
+    }
+
+    updated.package.push(LockedPackage {
+        name: name.to_owned(),
+        git: git_url,
+        reference: reference,
+        commit: commit,
+    });
+
+    Ok(checkout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_hash_is_fnv_1a() {
+        // The folders of every existing checkout are named with these, so they must not change
+        assert_eq!(url_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(url_hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(url_hash("https://example.com/json.git"), 0x1a9e_e112_9a68_cb28);
+    }
+}
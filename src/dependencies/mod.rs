@@ -0,0 +1,196 @@
+//! Turns the [dependencies] of a project file into the search directories and libraries
+//! handed to the compiler, building every maid project it depends on along the way.
+
+mod git;
+mod pkg_config;
+
+use std::path::PathBuf;
+use build::{self, BuildOptions, Built, CompileError, CompileErrorType, Database};
+use project::{Project, Target};
+
+/// Everything the compiler and linker need to know about a project's dependencies.
+#[derive(Clone, Default)]
+pub struct ResolvedDependencies {
+    pub header_search_directories: Vec<PathBuf>,
+    pub linker_search_directories: Vec<PathBuf>,
+    pub link_libraries: Vec<String>,
+    /// The libraries we built from other maid projects. When one of them changes, we relink.
+    pub built_libraries: Vec<PathBuf>,
+    /// Any other flags, like `-pthread` or `-D_REENTRANT` from pkg-config.
+    pub compile_flags: Vec<String>,
+    pub link_flags: Vec<String>,
+}
+
+impl ResolvedDependencies {
+    /// Adds everything from `other` that we do not already have.
+    pub fn extend(&mut self, other: ResolvedDependencies) {
+        fn merge<T: PartialEq>(into: &mut Vec<T>, from: Vec<T>) {
+            for item in from {
+                if !into.contains(&item) {
+                    into.push(item);
+                }
+            }
+        }
+
+        merge(&mut self.header_search_directories, other.header_search_directories);
+        merge(&mut self.linker_search_directories, other.linker_search_directories);
+        merge(&mut self.link_libraries, other.link_libraries);
+        merge(&mut self.built_libraries, other.built_libraries);
+        merge(&mut self.compile_flags, other.compile_flags);
+        merge(&mut self.link_flags, other.link_flags);
+    }
+
+    /// Every directory a dynamic library we link against may be in, which the dynamic
+    /// loader has to search when running what we linked.
+    pub fn library_directories(&self) -> Vec<PathBuf> {
+        let mut directories = self.linker_search_directories.clone();
+        for library in &self.built_libraries {
+            if let Some(directory) = library.parent() {
+                if !directories.iter().any(|d| d == directory) {
+                    directories.push(directory.to_path_buf());
+                }
+            }
+        }
+        directories
+    }
+}
+
+/// Collects the dependencies of `project`, building every maid project it depends on first.
+pub fn resolve(
+    project: &Project,
+    options: &BuildOptions,
+    parents: &mut Vec<PathBuf>,
+    built: &mut Built,
+    database: &mut Database,
+) -> Result<ResolvedDependencies, CompileError> {
+    let mut resolved = ResolvedDependencies::default();
+
+    // The libraries of the platform we cross compile for come first, so they are
+    // found before any library of this machine with the same name
+    if let Some(target) = project.target_config(options.triple.as_ref()) {
+        if let Some(ref directories) = target.header_search_directories {
+            for directory in directories {
+                resolved.header_search_directories.push(project.path(directory));
+            }
+        }
+        if let Some(ref directories) = target.linker_search_directories {
+            for directory in directories {
+                resolved.linker_search_directories.push(project.path(directory));
+            }
+        }
+        if let Some(ref libraries) = target.link_libraries {
+            resolved.link_libraries.extend(libraries.iter().cloned());
+        }
+    }
+
+    let dependencies = match project.dependencies {
+        Some(ref dependencies) => dependencies,
+        None => return Ok(resolved),
+    };
+
+    // The directories in the project file are relative to the project, not to us
+    if let Some(ref directories) = dependencies.header_search_directories {
+        for directory in directories {
+            resolved.header_search_directories.push(project.path(directory));
+        }
+    }
+    if let Some(ref directories) = dependencies.linker_search_directories {
+        for directory in directories {
+            resolved.linker_search_directories.push(project.path(directory));
+        }
+    }
+    if let Some(ref libraries) = dependencies.link_libraries {
+        resolved.link_libraries.extend(libraries.iter().cloned());
+    }
+
+    let lockfile = git::Lockfile::open(project)?;
+    let mut updated = git::Lockfile::default();
+
+    for (name, dependency) in &dependencies.projects {
+        // System libraries are not maid projects, so there is nothing to build
+        if let Some(ref package) = dependency.pkg_config {
+            if dependency.path.is_some() || dependency.git.is_some() {
+                return Err(CompileError {
+                    error_type: CompileErrorType::DependencyNotFound,
+                    msg: format!(
+                        "Dependency `{}` cannot have a `pkg-config` along with a `path` or `git`.",
+                        name
+                    ),
+                });
+            }
+
+            resolved.extend(pkg_config::probe(name, package, dependency.version.as_ref())?);
+            continue;
+        }
+
+        let root = match (&dependency.path, &dependency.git) {
+            (&Some(ref path), &None) => project.path(path),
+            (&None, &Some(_)) => git::checkout(project, name, dependency, &lockfile, &mut updated)?,
+            (&Some(_), &Some(_)) => {
+                return Err(CompileError {
+                    error_type: CompileErrorType::DependencyNotFound,
+                    msg: format!("Dependency `{}` cannot have both a `path` and a `git`.", name),
+                })
+            }
+            (&None, &None) => {
+                return Err(CompileError {
+                    error_type: CompileErrorType::DependencyNotFound,
+                    msg: format!("Dependency `{}` does not specify where to find it.", name),
+                })
+            }
+        };
+
+        let library = match Project::open(&root) {
+            Ok(library) => library,
+            Err(e) => {
+                return Err(CompileError {
+                    error_type: CompileErrorType::DependencyNotFound,
+                    msg: format!("Dependency `{}`: {}", name, e.description),
+                })
+            }
+        };
+
+        // We can only link against libraries
+        if library.package.target == Target::Executable {
+            return Err(CompileError {
+                error_type: CompileErrorType::DependencyIsNotALibrary,
+                msg: format!(
+                    "Dependency `{}` is an Executable, but must be a Static or Dynamic library.",
+                    name
+                ),
+            });
+        }
+
+        // Compare canonical paths, as `../a` and `../b/../a` are the same project
+        let canonical = root.canonicalize().unwrap_or_else(|_| root.clone());
+        if parents.contains(&canonical) {
+            return Err(CompileError {
+                error_type: CompileErrorType::DependencyCycle,
+                msg: format!("Dependency `{}` depends on itself.", name),
+            });
+        }
+
+        parents.push(canonical);
+        let transitive = build::build_project(&library, options, parents, built, database)?;
+        parents.pop();
+
+        // Its public headers, and the library we just built
+        resolved.extend(ResolvedDependencies {
+            header_search_directories: vec![library.path("include")],
+            linker_search_directories: vec![options.output_directory()],
+            link_libraries: vec![library.package.name.clone()],
+            built_libraries: vec![options.output_directory().join(library.output_name(options.os()))],
+            ..ResolvedDependencies::default()
+        });
+
+        // A static library does not carry its own dependencies, so we link them too
+        resolved.extend(transitive);
+    }
+
+    // Only touch Maid.lock when a git dependency was added, removed, or changed
+    if updated != lockfile {
+        updated.save(project)?;
+    }
+
+    Ok(resolved)
+}
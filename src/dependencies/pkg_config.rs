@@ -0,0 +1,85 @@
+//! System libraries found through pkg-config, like `sdl2 = { pkg-config = "sdl2", version = ">=2.0" }`.
+
+use std::path::PathBuf;
+use build::{CompileError, CompileErrorType};
+use command::CommandLine;
+use super::ResolvedDependencies;
+
+/// Runs pkg-config with the given arguments, returning what it printed.
+fn pkg_config(name: &str, args: &[&str]) -> Result<String, CompileError> {
+    let mut command = CommandLine::new("pkg-config");
+    command.args(args.iter().cloned());
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(e) => {
+            return Err(CompileError {
+                error_type: CompileErrorType::PkgConfigFailed,
+                msg: format!("Dependency `{}`: failed to execute `{}`: {}", name, command.display(), e),
+            })
+        }
+    };
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    } else {
+        Err(CompileError {
+            error_type: CompileErrorType::PkgConfigFailed,
+            msg: format!(
+                "Dependency `{}`: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        })
+    }
+}
+
+/// Turns a requirement like `>=2.0` into the `sdl2 >= 2.0` pkg-config expects.
+fn requirement(package: &str, version: Option<&String>) -> String {
+    match version {
+        Some(version) => {
+            let version = version.trim();
+            let split = version
+                .find(|c: char| c != '<' && c != '>' && c != '=' && c != '!')
+                .unwrap_or(version.len());
+            // A bare version means exactly that version
+            let operator = if split == 0 { "=" } else { &version[..split] };
+            format!("{} {} {}", package, operator, version[split..].trim())
+        }
+        None => package.to_owned(),
+    }
+}
+
+/// Asks pkg-config for the flags needed to compile and link against `package`.
+pub fn probe(
+    name: &str,
+    package: &str,
+    version: Option<&String>,
+) -> Result<ResolvedDependencies, CompileError> {
+    // Fails with a message like "Requested 'sdl2 >= 3.0' but version of SDL2 is 2.0.20"
+    pkg_config(
+        name,
+        &["--print-errors", "--exists", &requirement(package, version)],
+    )?;
+
+    let mut resolved = ResolvedDependencies::default();
+
+    for flag in pkg_config(name, &["--cflags", package])?.split_whitespace() {
+        if let Some(directory) = flag.strip_prefix("-I") {
+            resolved.header_search_directories.push(PathBuf::from(directory));
+        } else {
+            resolved.compile_flags.push(flag.to_owned());
+        }
+    }
+
+    for flag in pkg_config(name, &["--libs", package])?.split_whitespace() {
+        if let Some(directory) = flag.strip_prefix("-L") {
+            resolved.linker_search_directories.push(PathBuf::from(directory));
+        } else if let Some(library) = flag.strip_prefix("-l") {
+            resolved.link_libraries.push(library.to_owned());
+        } else {
+            resolved.link_flags.push(flag.to_owned());
+        }
+    }
+
+    Ok(resolved)
+}
@@ -0,0 +1,272 @@
+//! Installs what the projects of a workspace build into the usual layout under a prefix,
+//! like `/usr/local`: executables into `bin/`, libraries into `lib/`, and the public
+//! headers of libraries into `include/<name>/`.
+//!
+//! Every file installed for a project is listed in its manifest, which is installed along
+//! with it as `share/maid/installed/<name>.toml`. `maid uninstall` reads the manifest to
+//! remove exactly those files again, and nothing else.
+
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use build::Os;
+use project::{executable_name, Project, Target};
+use utils;
+use ansi_term::Color::Green;
+
+/// Where everything is installed: under `prefix`, which is itself inside of `destdir` when
+/// there is one. A `destdir` stages the files for packaging, without touching the system.
+pub struct Layout {
+    prefix: PathBuf,
+    destdir: Option<PathBuf>,
+}
+
+impl Layout {
+    /// Both paths are used as they are, so relative ones have to be made absolute by the
+    /// caller while still in the directory they are relative to.
+    pub fn new(prefix: &Path, destdir: Option<&Path>) -> Layout {
+        Layout {
+            prefix: prefix.to_path_buf(),
+            destdir: destdir.map(|destdir| destdir.to_path_buf()),
+        }
+    }
+
+    /// The real location of `path`, which is relative to the prefix, like `bin/hello`.
+    fn path(&self, path: &Path) -> PathBuf {
+        let full = self.prefix.join(path);
+        match self.destdir {
+            // The prefix stays the same within the destdir, so its root is dropped to put
+            // it inside of the destdir, like `stage/usr/local` for `/usr/local`
+            Some(ref destdir) => {
+                let mut staged = destdir.clone();
+                for component in full.components() {
+                    match component {
+                        Component::Prefix(_) | Component::RootDir => {}
+                        _ => staged.push(component.as_os_str()),
+                    }
+                }
+                staged
+            }
+            None => full,
+        }
+    }
+
+    /// Where the manifest of a project is, relative to the prefix.
+    fn manifest_path(project: &Project) -> PathBuf {
+        Path::new("share")
+            .join("maid")
+            .join("installed")
+            .join(format!("{}.toml", project.package.name))
+    }
+}
+
+/// Everything installed for a project, relative to the prefix.
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    version: String,
+    files: Vec<PathBuf>,
+    /// The directories made for the project alone, like `include/<name>`. They are removed
+    /// once they are empty.
+    directories: Vec<PathBuf>,
+}
+
+impl Manifest {
+    fn load(path: &Path) -> Option<Manifest> {
+        let contents = fs::read_to_string(path).ok()?;
+        ::toml::from_str(&contents).ok()
+    }
+}
+
+/// Removes a file or a symbolic link, if there is one.
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(_) => fs::remove_file(path),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// An installation in progress, which records every file it writes.
+struct Installation<'a> {
+    layout: &'a Layout,
+    manifest: Manifest,
+}
+
+impl<'a> Installation<'a> {
+    /// Makes sure the directory of `path` exists, returning the real location of `path`.
+    fn prepare(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let real = self.layout.path(path);
+        let mut dir_builder = DirBuilder::new();
+        dir_builder.recursive(true);
+        dir_builder.create(real.parent().unwrap())?;
+
+        // Whatever was there before goes, as copying onto a symbolic link would write
+        // through it, into the file it points to
+        remove(&real)?;
+
+        println!("  {} {}", Green.paint("Installing"), real.display());
+        self.manifest.files.push(path.to_path_buf());
+        Ok(real)
+    }
+
+    /// Copies `source` to `path`, keeping its permissions.
+    fn copy(&mut self, source: &Path, path: &Path) -> io::Result<()> {
+        if !source.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("`{}` was not built.", source.display()),
+            ));
+        }
+        let real = self.prepare(path)?;
+        fs::copy(source, real).map(|_| ())
+    }
+
+    /// Links `path` to `target`, which is a file name in the same directory.
+    fn link(&mut self, target: &str, path: &Path) -> io::Result<()> {
+        let real = self.prepare(path)?;
+        utils::symlink(Path::new(target), &real)
+    }
+}
+
+/// The names of a dynamic library on Unix, from the real file to the symbolic links to it:
+/// `libz.so.1.2.11`, `libz.so.1` (its soname), and `libz.so` for the linker.
+fn library_names(name: &str, version: &str, os: Os) -> Vec<String> {
+    let major = version.split('.').next().unwrap_or(version);
+    let mut names = match os {
+        Os::MacOS => vec![
+            format!("lib{}.{}.dylib", name, version),
+            format!("lib{}.{}.dylib", name, major),
+            format!("lib{}.dylib", name),
+        ],
+        _ => vec![
+            format!("lib{}.so.{}", name, version),
+            format!("lib{}.so.{}", name, major),
+            format!("lib{}.so", name),
+        ],
+    };
+    // A version like `1` has no minor version, so the real file already has the soname
+    names.dedup();
+    names
+}
+
+/// Installs every file a project built into `output_directory` for `os`, and its manifest.
+/// The files of a previous installation which are not part of this one are removed.
+pub fn install(project: &Project, output_directory: &Path, os: Os, layout: &Layout) -> io::Result<()> {
+    let mut installation = Installation {
+        layout: layout,
+        manifest: Manifest {
+            version: project.package.version.clone(),
+            ..Manifest::default()
+        },
+    };
+
+    // Every executable, including those a library may have in source/bin/
+    let binaries = match project.binaries() {
+        Ok(binaries) => binaries,
+        Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e.description)),
+    };
+    for binary in binaries {
+        let name = executable_name(&binary.name, os);
+        installation.copy(&output_directory.join(&name), &Path::new("bin").join(&name))?;
+    }
+
+    let output = output_directory.join(project.output_name(os));
+    match project.package.target {
+        Target::Executable => {}
+        Target::Static => {
+            installation.copy(&output, &Path::new("lib").join(project.output_name(os)))?;
+        }
+        Target::Dynamic => match os {
+            Os::Windows => {
+                // A DLL is found beside the executables, and linked through its import library
+                installation.copy(&output, &Path::new("bin").join(project.output_name(os)))?;
+                let import_library = output.with_extension("lib");
+                if import_library.is_file() {
+                    let name = format!("{}.lib", project.package.name);
+                    installation.copy(&import_library, &Path::new("lib").join(name))?;
+                }
+            }
+            _ => {
+                let names = library_names(&project.package.name, &project.package.version, os);
+                installation.copy(&output, &Path::new("lib").join(&names[0]))?;
+                // Each name points to the one before it
+                for pair in names.windows(2) {
+                    installation.link(&pair[0], &Path::new("lib").join(&pair[1]))?;
+                }
+            }
+        },
+    }
+
+    // The public headers of a library, kept apart from those of every other library
+    let include_directory = project.root.join("include");
+    if project.package.target != Target::Executable && include_directory.is_dir() {
+        let headers_directory = Path::new("include").join(&project.package.name);
+        installation.manifest.directories.push(headers_directory.clone());
+
+        for path in utils::get_files_in_directory(&include_directory, true) {
+            let relative = path.strip_prefix(&include_directory).unwrap().to_path_buf();
+            if path.is_dir() {
+                installation.manifest.directories.push(headers_directory.join(relative));
+            } else {
+                installation.copy(&path, &headers_directory.join(relative))?;
+            }
+        }
+    }
+
+    // Anything installed before which is not anymore, like the library of an older version
+    let manifest_path = Layout::manifest_path(project);
+    let mut manifest = installation.manifest;
+    if let Some(previous) = Manifest::load(&layout.path(&manifest_path)) {
+        for file in &previous.files {
+            if !manifest.files.contains(file) {
+                remove(&layout.path(file))?;
+            }
+        }
+        for directory in previous.directories {
+            if !manifest.directories.contains(&directory) {
+                manifest.directories.push(directory);
+            }
+        }
+        remove_empty_directories(layout, &manifest.directories);
+    }
+
+    let real = layout.path(&manifest_path);
+    let mut dir_builder = DirBuilder::new();
+    dir_builder.recursive(true);
+    dir_builder.create(real.parent().unwrap())?;
+    utils::write_if_changed(&real, &::toml::to_string(&manifest).unwrap())
+}
+
+/// Removes those of `directories` which are empty, the deepest first.
+fn remove_empty_directories(layout: &Layout, directories: &[PathBuf]) {
+    let mut directories = directories.to_vec();
+    directories.sort_by_key(|directory| ::std::cmp::Reverse(directory.components().count()));
+    for directory in directories {
+        // A directory which is not empty still has something of someone else in it
+        let _ = fs::remove_dir(layout.path(&directory));
+    }
+}
+
+/// Removes everything the manifest of a project lists, and then the manifest itself.
+/// Returns false when the project was not installed.
+pub fn uninstall(project: &Project, layout: &Layout) -> io::Result<bool> {
+    let manifest_path = Layout::manifest_path(project);
+    let manifest = match Manifest::load(&layout.path(&manifest_path)) {
+        Some(manifest) => manifest,
+        None => return Ok(false),
+    };
+
+    for file in &manifest.files {
+        let real = layout.path(file);
+        println!("    {} {}", Green.paint("Removing"), real.display());
+        remove(&real)?;
+    }
+    remove(&layout.path(&manifest_path))?;
+
+    let mut directories = manifest.directories;
+    directories.push(manifest_path.parent().unwrap().to_path_buf());
+    directories.push(Path::new("share").join("maid"));
+    remove_empty_directories(layout, &directories);
+
+    Ok(true)
+}
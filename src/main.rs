@@ -1,4 +1,5 @@
 extern crate ansi_term;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate structopt;
@@ -6,13 +7,19 @@ extern crate structopt;
 extern crate structopt_derive;
 extern crate toml;
 
+mod bench;
 mod build;
+mod command;
+mod dependencies;
+mod install;
 mod project;
+mod test;
 mod utils;
 mod user;
 
+use std::path::PathBuf;
 use structopt::StructOpt;
-use project::Project;
+use project::{Project, Workspace};
 use ansi_term::Color::Green;
 
 #[derive(StructOpt)]
@@ -33,16 +40,154 @@ enum Options {
         verbose: bool,
 
         #[structopt(short = "r", long = "release")]
-        /// Compiles with all optimizations
+        /// Compiles with all optimizations (the same as `--profile release`)
         release: bool,
+
+        #[structopt(long = "profile")]
+        /// The profile to build with, like `debug`, `release`, or one from [profile.<name>]
+        profile: Option<String>,
+
+        #[structopt(short = "j", long = "jobs")]
+        /// The number of files to compile at the same time (defaults to the number of CPUs)
+        jobs: Option<usize>,
+
+        #[structopt(long = "target")]
+        /// The platform to cross compile for, like `aarch64-linux-gnu`
+        target: Option<String>,
+
+        #[structopt(long = "bin")]
+        /// Only links the binary of this name, from [[bin]] or source/bin/
+        bin: Option<String>,
+
+        #[structopt(long = "examples")]
+        /// Also builds every example in examples/
+        examples: bool,
+
+        #[structopt(long = "manifest-path", parse(from_os_str))]
+        /// The Maid.toml to use, instead of searching the current directory and those above it
+        manifest_path: Option<PathBuf>,
     },
     #[structopt(name = "run")]
     Run {
-        /// Arguments to pass to the binary on execution (use "quotes")
-        arguments: Option<String>,
+        #[structopt(long = "bin")]
+        /// The binary to run, when the project has more than one
+        bin: Option<String>,
+
+        #[structopt(long = "example")]
+        /// Runs the example of this name from examples/, like `parse` or `my-lib/parse`
+        example: Option<String>,
+
+        #[structopt(short = "r", long = "release")]
+        /// Builds with all optimizations (the same as `--profile release`)
+        release: bool,
+
+        #[structopt(long = "profile")]
+        /// The profile to build with, like `debug`, `release`, or one from [profile.<name>]
+        profile: Option<String>,
+
+        /// Arguments to pass to the binary on execution, exactly as they are (put them after `--`)
+        arguments: Vec<String>,
+
+        #[structopt(long = "manifest-path", parse(from_os_str))]
+        /// The Maid.toml to use, instead of searching the current directory and those above it
+        manifest_path: Option<PathBuf>,
+    },
+    #[structopt(name = "test")]
+    /// Builds and runs every test in tests/
+    Test {
+        /// Only runs the tests whose name contains this, like `parser` or `my-lib/`
+        filter: Option<String>,
+
+        #[structopt(short = "v", long = "verbose")]
+        /// Gives you more information as to what is happening
+        verbose: bool,
+
+        #[structopt(short = "r", long = "release")]
+        /// Builds the tests with all optimizations (the same as `--profile release`)
+        release: bool,
+
+        #[structopt(long = "profile")]
+        /// The profile to build the tests with
+        profile: Option<String>,
+
+        #[structopt(short = "j", long = "jobs")]
+        /// The number of files to compile, and tests to run, at the same time
+        jobs: Option<usize>,
+
+        #[structopt(long = "timeout")]
+        /// The number of seconds a test may run for before it is killed (defaults to 60)
+        timeout: Option<u64>,
+
+        #[structopt(long = "manifest-path", parse(from_os_str))]
+        /// The Maid.toml to use, instead of searching the current directory and those above it
+        manifest_path: Option<PathBuf>,
+    },
+    #[structopt(name = "bench")]
+    /// Builds every benchmark in benches/ with the release profile, and times them
+    Bench {
+        /// Only runs the benchmarks whose name contains this
+        filter: Option<String>,
+
+        #[structopt(short = "v", long = "verbose")]
+        /// Gives you more information as to what is happening
+        verbose: bool,
+
+        #[structopt(long = "runs")]
+        /// The number of times to run each benchmark (defaults to 10)
+        runs: Option<usize>,
+
+        #[structopt(short = "j", long = "jobs")]
+        /// The number of files to compile at the same time (defaults to the number of CPUs)
+        jobs: Option<usize>,
+
+        #[structopt(long = "manifest-path", parse(from_os_str))]
+        /// The Maid.toml to use, instead of searching the current directory and those above it
+        manifest_path: Option<PathBuf>,
+    },
+    #[structopt(name = "install")]
+    /// Builds with the release profile, and installs every project into bin/, lib/, and include/
+    Install {
+        #[structopt(long = "prefix", parse(from_os_str))]
+        /// The directory to install into (defaults to /usr/local)
+        prefix: Option<PathBuf>,
+
+        #[structopt(long = "destdir", parse(from_os_str))]
+        /// Stages the installation inside of this directory instead, like for packaging
+        destdir: Option<PathBuf>,
+
+        #[structopt(short = "v", long = "verbose")]
+        /// Gives you more information as to what is happening
+        verbose: bool,
+
+        #[structopt(short = "j", long = "jobs")]
+        /// The number of files to compile at the same time (defaults to the number of CPUs)
+        jobs: Option<usize>,
+
+        #[structopt(long = "manifest-path", parse(from_os_str))]
+        /// The Maid.toml to use, instead of searching the current directory and those above it
+        manifest_path: Option<PathBuf>,
+    },
+    #[structopt(name = "uninstall")]
+    /// Removes every file `maid install` installed into a prefix
+    Uninstall {
+        #[structopt(long = "prefix", parse(from_os_str))]
+        /// The directory that was installed into (defaults to /usr/local)
+        prefix: Option<PathBuf>,
+
+        #[structopt(long = "destdir", parse(from_os_str))]
+        /// The directory the installation was staged in
+        destdir: Option<PathBuf>,
+
+        #[structopt(long = "manifest-path", parse(from_os_str))]
+        /// The Maid.toml to use, instead of searching the current directory and those above it
+        manifest_path: Option<PathBuf>,
     },
     #[structopt(name = "clean")]
-    Clean,
+    Clean {
+        #[structopt(long = "manifest-path", parse(from_os_str))]
+        /// The Maid.toml to use, instead of searching the current directory and those above it
+        manifest_path: Option<PathBuf>,
+    },
 }
 
 fn main() {
@@ -62,52 +207,264 @@ fn main() {
 
     match options {
         Options::New { name, lib } => {
-            match Project::new(&name) {
+            match Project::new(&name, lib) {
                 Err(e) => panic!("{}", e),
                 _ => {}
             }
         }
-        Options::Build { verbose, release } => build::build(release, verbose).unwrap(),
-        Options::Run { arguments } => {
+        Options::Build { verbose, release, profile, jobs, target, bin, examples, manifest_path } => {
+            let workspace = Workspace::get(manifest_path.as_ref().map(|p| p.as_path())).unwrap();
+            if let Some(ref bin) = bin {
+                if let Err(e) = workspace.binary(Some(bin)) {
+                    panic!("{}", e.description);
+                }
+            }
+            let profile = match profile {
+                Some(profile) => profile,
+                None => String::from(if release { "release" } else { "debug" }),
+            };
+            let options = build::BuildOptions {
+                profile: build::Profile::resolve(&profile, &workspace.profiles).unwrap(),
+                verbose: verbose,
+                jobs: jobs.unwrap_or_else(utils::cpu_count),
+                target_directory: workspace.target_directory(),
+                triple: target.map(|target| build::Triple::parse(&target).unwrap()),
+                binary: bin,
+            };
+            if examples {
+                // The projects are built along with their examples
+                build::programs::build(&workspace, &options, build::programs::Kind::Examples).unwrap();
+            } else {
+                build::build(&workspace, &options).unwrap();
+            }
+        }
+        Options::Run { bin, example, release, profile, arguments, manifest_path } => {
+            // Finding the workspace moves into its root, but the program runs from where we
+            // were run, unless it says otherwise
+            let invoked_from = std::env::current_dir().unwrap();
+
             // Get the project file
-            let project = Project::get().unwrap();
+            let workspace = Workspace::get(manifest_path.as_ref().map(|p| p.as_path())).unwrap();
+            let profile = match profile {
+                Some(profile) => profile,
+                None => String::from(if release { "release" } else { "debug" }),
+            };
 
-            // Unwrap the program arguments
-            let arguments = match arguments {
-                Some(v) => v,
-                None => String::from(""),
+            // Build the program without verbosity
+            let mut options = build::BuildOptions {
+                profile: build::Profile::resolve(&profile, &workspace.profiles).unwrap(),
+                verbose: false,
+                jobs: utils::cpu_count(),
+                target_directory: workspace.target_directory(),
+                triple: None,
+                binary: None,
             };
 
-            // Build the program in debug mode, without verbosity
-            build::build(false, false).unwrap();
+            let (project, name, path, library_directories) = match example {
+                Some(example) => {
+                    if bin.is_some() {
+                        panic!("Only one of `--bin` and `--example` can be run.");
+                    }
+                    let programs =
+                        build::programs::build(&workspace, &options, build::programs::Kind::Examples)
+                            .unwrap();
+                    // Examples may be named with their package, when several have the same name
+                    let program = programs.into_iter().find(|program| {
+                        program.name == example
+                            || format!("{}/{}", program.package, program.name) == example
+                    });
+                    let program = match program {
+                        Some(program) => program,
+                        None => panic!("There is no example named `{}` in this workspace.", example),
+                    };
+                    let project = workspace
+                        .members
+                        .iter()
+                        .find(|member| member.package.name == program.package)
+                        .unwrap();
+                    (project, program.name, program.path, program.library_directories)
+                }
+                None => {
+                    let (project, binary) = match workspace.binary(bin.as_ref().map(|b| b.as_str())) {
+                        Ok(found) => found,
+                        Err(e) => panic!("{}", e.description),
+                    };
+                    options.binary = Some(binary.name.clone());
+                    let dependencies = build::build(&workspace, &options).unwrap();
 
-            if project.package.target != project::Target::Executable {
-                // Prevent them from being able to run the program if it is not executable
-                panic!("Can't execute {:?} targets.", project.package.target);
-            } else {
-                println!("     {} `{}`", Green.paint("Running"), project.package.name);
-
-                // Execute the generated binary
-                let result = if cfg!(target_os = "windows") {
-                    utils::shell_command(
-                        &format!("./target/debug/{}.exe {}", project.package.name, arguments),
-                        false,
-                    )
-                } else {
-                    utils::shell_command(
-                        &format!("./target/debug/{} {}", project.package.name, arguments),
-                        false,
-                    )
-                }.unwrap();
-
-                if result.success() == false {
-                    println!("Exited with code: {}", result.code().unwrap());
+                    // The binary's own libraries are beside it, and those of its dependencies
+                    // wherever they were found or built
+                    let member = workspace
+                        .members
+                        .iter()
+                        .position(|member| member.package.name == project.package.name)
+                        .unwrap();
+                    let mut library_directories = vec![options.output_directory()];
+                    library_directories.extend(dependencies[member].library_directories());
+
+                    let path = options
+                        .output_directory()
+                        .join(project::executable_name(&binary.name, options.os()));
+                    (project, binary.name, path, library_directories)
                 }
+            };
+
+            // The program may be run from another directory, which relative paths would not survive
+            let path = utils::absolute_path(&path);
+            let library_directories: Vec<PathBuf> = library_directories
+                .iter()
+                .map(|directory| utils::absolute_path(directory))
+                .collect();
+
+            println!("     {} `{}`", Green.paint("Running"), name);
+
+            // Execute the generated binary, letting it find the dynamic libraries it was
+            // linked against without them being installed
+            let mut command = std::process::Command::new(&path);
+            command
+                .args(&arguments)
+                .env(test::library_path_variable(), test::library_path(&library_directories));
+            let run = project.run.as_ref();
+            match run.and_then(|run| run.working_directory.as_ref()) {
+                Some(directory) => command.current_dir(project.path(directory)),
+                None => command.current_dir(&invoked_from),
+            };
+            if let Some(variables) = run.and_then(|run| run.env.as_ref()) {
+                command.envs(variables);
+            }
+
+            let status = match command.status() {
+                Ok(status) => status,
+                Err(e) => panic!("Failed to execute `{}`: {}", path.display(), e),
+            };
+            exit_with(status);
+        }
+        Options::Test { filter, verbose, release, profile, jobs, timeout, manifest_path } => {
+            let workspace = Workspace::get(manifest_path.as_ref().map(|p| p.as_path())).unwrap();
+            let profile = match profile {
+                Some(profile) => profile,
+                None => String::from(if release { "release" } else { "debug" }),
+            };
+            let jobs = jobs.unwrap_or_else(utils::cpu_count);
+            let options = build::BuildOptions {
+                profile: build::Profile::resolve(&profile, &workspace.profiles).unwrap(),
+                verbose: verbose,
+                jobs: jobs,
+                target_directory: workspace.target_directory(),
+                triple: None,
+                binary: None,
+            };
+            let programs =
+                build::programs::build(&workspace, &options, build::programs::Kind::Tests).unwrap();
+
+            let timeout = std::time::Duration::from_secs(timeout.unwrap_or(60));
+            if !test::run(&programs, filter.as_ref().map(|f| f.as_str()), jobs, timeout) {
+                std::process::exit(1);
             }
         }
-        Options::Clean => match project::Project::get() {
-            Ok(_) => std::fs::remove_dir_all("./target").unwrap(),
+        Options::Bench { filter, verbose, runs, jobs, manifest_path } => {
+            let workspace = Workspace::get(manifest_path.as_ref().map(|p| p.as_path())).unwrap();
+            let options = build::BuildOptions {
+                profile: build::Profile::resolve("release", &workspace.profiles).unwrap(),
+                verbose: verbose,
+                jobs: jobs.unwrap_or_else(utils::cpu_count),
+                target_directory: workspace.target_directory(),
+                triple: None,
+                binary: None,
+            };
+            let programs =
+                build::programs::build(&workspace, &options, build::programs::Kind::Benches).unwrap();
+
+            let directory = workspace.target_directory().join("bench");
+            let filter = filter.as_ref().map(|f| f.as_str());
+            if !bench::run(&programs, filter, runs.unwrap_or(10).max(1), &directory) {
+                std::process::exit(1);
+            }
+        }
+        Options::Install { prefix, destdir, verbose, jobs, manifest_path } => {
+            // Finding the workspace moves into its root, so relative paths are made absolute first
+            let prefix = utils::absolute_path(&prefix.unwrap_or_else(|| PathBuf::from("/usr/local")));
+            let destdir = destdir.map(|destdir| utils::absolute_path(&destdir));
+
+            let workspace = Workspace::get(manifest_path.as_ref().map(|p| p.as_path())).unwrap();
+            let options = build::BuildOptions {
+                profile: build::Profile::resolve("release", &workspace.profiles).unwrap(),
+                verbose: verbose,
+                jobs: jobs.unwrap_or_else(utils::cpu_count),
+                target_directory: workspace.target_directory(),
+                triple: None,
+                binary: None,
+            };
+            build::build(&workspace, &options).unwrap();
+
+            let layout = install::Layout::new(&prefix, destdir.as_ref().map(|d| d.as_path()));
+            for member in &workspace.members {
+                if let Err(e) = install::install(member, &options.output_directory(), options.os(), &layout) {
+                    panic!("Failed to install `{}`: {}", member.package.name, e);
+                }
+            }
+        }
+        Options::Uninstall { prefix, destdir, manifest_path } => {
+            // Finding the workspace moves into its root, so relative paths are made absolute first
+            let prefix = utils::absolute_path(&prefix.unwrap_or_else(|| PathBuf::from("/usr/local")));
+            let destdir = destdir.map(|destdir| utils::absolute_path(&destdir));
+
+            let workspace = Workspace::get(manifest_path.as_ref().map(|p| p.as_path())).unwrap();
+            let layout = install::Layout::new(&prefix, destdir.as_ref().map(|d| d.as_path()));
+            for member in &workspace.members {
+                match install::uninstall(member, &layout) {
+                    Ok(true) => {}
+                    Ok(false) => eprintln!(
+                        "`{}` is not installed in `{}`.",
+                        member.package.name,
+                        prefix.display()
+                    ),
+                    Err(e) => panic!("Failed to uninstall `{}`: {}", member.package.name, e),
+                }
+            }
+        }
+        Options::Clean { manifest_path } => match Workspace::get(manifest_path.as_ref().map(|p| p.as_path())) {
+            Ok(workspace) => {
+                // Members may also have been built on their own, outside of the workspace
+                let mut directories = vec![workspace.target_directory()];
+                for member in &workspace.members {
+                    directories.push(member.root.join("target"));
+                }
+
+                for directory in directories {
+                    if directory.is_dir() {
+                        std::fs::remove_dir_all(directory).unwrap();
+                    }
+                }
+            }
             Err(e) => panic!("{}", e.description),
         },
     }
 }
+
+/// Exits with the same status as a program we ran, so scripts running it through us see
+/// how it went. A program killed by a signal exits the way a shell reports it, with 128
+/// plus the number of the signal.
+fn exit_with(status: std::process::ExitStatus) -> ! {
+    match status.code() {
+        Some(0) => std::process::exit(0),
+        Some(code) => {
+            eprintln!("Exited with code: {}", code);
+            std::process::exit(code);
+        }
+        None => {}
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            eprintln!("Killed by signal: {}", signal);
+            std::process::exit(128 + signal);
+        }
+    }
+
+    // Every way of finishing without an exit code has been covered above
+    eprintln!("Exited with: {}", status);
+    std::process::exit(1);
+}
@@ -1,12 +1,34 @@
+use std::collections::BTreeMap;
+use std::env;
 use std::fs::{DirBuilder, File};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as DeError;
+use serde::ser::SerializeMap;
+use build::{Language, Os, Triple};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Project {
     pub package: Package,
     pub build: Option<Build>,
     pub dependencies: Option<Dependencies>,
+    pub workspace: Option<WorkspaceConfig>,
+    /// Custom build profiles, like [profile.asan], and changes to [profile.debug] and [profile.release].
+    pub profile: Option<BTreeMap<String, ProfileConfig>>,
+    /// Programs to build with instead of the usual ones of the compiler.
+    pub toolchain: Option<ToolchainConfig>,
+    /// Settings for cross compiling with `--target`, like [target.aarch64-linux-gnu].
+    pub target: Option<BTreeMap<String, TargetConfig>>,
+    /// More executables, each built from a source with its own `main`, like
+    /// [[bin]] with `name = "convert"` and `path = "tools/convert.c"`.
+    pub bin: Option<Vec<BinConfig>>,
+    /// How `maid run` runs the project's executables and examples.
+    pub run: Option<RunConfig>,
+    /// The directory containing this project's Maid.toml. Every path in the project
+    /// file is relative to it.
+    #[serde(skip)]
+    pub root: PathBuf,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -15,20 +37,244 @@ pub struct Package {
     pub version: String,
     pub authors: Vec<String>,
     pub target: Target,
+    /// The language standard of the sources, like `c11` or `gnu++17`. Without one,
+    /// the compiler's default is used.
+    pub standard: Option<Standards>,
+}
+
+/// Either `standard = "c11"`, or one standard per language, like `standard = ["c11", "c++17"]`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Standards {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Standards {
+    pub fn names(&self) -> Vec<String> {
+        match *self {
+            Standards::One(ref name) => vec![name.clone()],
+            Standards::Many(ref names) => names.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Build {
     pub preferred_compiler: Option<::build::Compiler>,
+    /// Which warnings the compiler reports. Defaults to whatever the compiler does by itself.
+    pub warnings: Option<Warnings>,
+    /// Turns every warning into an error, so they cannot be ignored (in CI, for example).
+    pub warnings_as_errors: Option<bool>,
+    /// The NASM compatible assembler for `.asm` sources. Defaults to `nasm`.
+    pub assembler: Option<String>,
+    /// The object format `.asm` sources are assembled into, like `elf32`. Defaults to the
+    /// format of the platform we are building for.
+    pub assembler_format: Option<String>,
+    pub assembler_options: Option<Vec<String>>,
     pub gnu_options: Option<Vec<String>>,
     pub clang_options: Option<Vec<String>>,
+    /// Options given to cl.exe when compiling with MSVC.
+    pub msvc_options: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BinConfig {
+    pub name: String,
+    /// The source with the `main` of the executable, relative to the project.
+    pub path: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+pub struct RunConfig {
+    /// The directory to run from, relative to the project. Defaults to the directory
+    /// maid is run from.
+    pub working_directory: Option<String>,
+    /// Variables to set for the program, like `env = { LOG_LEVEL = "debug" }`.
+    pub env: Option<BTreeMap<String, String>>,
+}
+
+/// An executable a project builds, which is linked from its own source along with every
+/// source of the project that is not the `main` of another executable.
+#[derive(Clone, Debug)]
+pub struct Binary {
+    pub name: String,
+    pub source: PathBuf,
+}
+
+#[derive(Clone)]
 pub struct Dependencies {
     pub header_search_directories: Option<Vec<String>>,
     pub linker_search_directories: Option<Vec<String>>,
     pub link_libraries: Option<Vec<String>>,
+    /// Every other entry of [dependencies], like `mathlib = { path = "../mathlib" }`.
+    pub projects: BTreeMap<String, Dependency>,
+}
+
+/// Another maid project this project is built against.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Dependency {
+    /// The directory of a project on this machine, relative to this project.
+    pub path: Option<String>,
+    /// The URL of a git repository containing a project.
+    pub git: Option<String>,
+    /// Which commit, tag, or branch of `git` to use. Without any, the default branch is used.
+    pub rev: Option<String>,
+    pub tag: Option<String>,
+    pub branch: Option<String>,
+    /// The name of a system library known to pkg-config, like `sdl2`.
+    #[serde(rename = "pkg-config")]
+    pub pkg_config: Option<String>,
+    /// The versions of the pkg-config library we accept, like `>=2.0`.
+    pub version: Option<String>,
+}
+
+// The search directories and named dependencies share the [dependencies] table, so we
+// split them apart by hand.
+impl<'de> Deserialize<'de> for Dependencies {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut table = BTreeMap::<String, ::toml::Value>::deserialize(deserializer)?;
+
+        fn take<E: DeError>(
+            table: &mut BTreeMap<String, ::toml::Value>,
+            key: &str,
+        ) -> Result<Option<Vec<String>>, E> {
+            match table.remove(key) {
+                Some(value) => value.try_into().map(Some).map_err(E::custom),
+                None => Ok(None),
+            }
+        }
+
+        let header_search_directories = take(&mut table, "header_search_directories")?;
+        let linker_search_directories = take(&mut table, "linker_search_directories")?;
+        let link_libraries = take(&mut table, "link_libraries")?;
+
+        let mut projects = BTreeMap::new();
+        for (name, value) in table {
+            let dependency: Dependency = value
+                .try_into()
+                .map_err(|e| D::Error::custom(format!("dependency `{}`: {}", name, e)))?;
+            projects.insert(name, dependency);
+        }
+
+        Ok(Dependencies {
+            header_search_directories: header_search_directories,
+            linker_search_directories: linker_search_directories,
+            link_libraries: link_libraries,
+            projects: projects,
+        })
+    }
+}
+
+impl Serialize for Dependencies {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        // TOML needs plain values before tables, so the directories come first
+        if let Some(ref directories) = self.header_search_directories {
+            map.serialize_entry("header_search_directories", directories)?;
+        }
+        if let Some(ref directories) = self.linker_search_directories {
+            map.serialize_entry("linker_search_directories", directories)?;
+        }
+        if let Some(ref libraries) = self.link_libraries {
+            map.serialize_entry("link_libraries", libraries)?;
+        }
+        for (name, dependency) in &self.projects {
+            map.serialize_entry(name, dependency)?;
+        }
+        map.end()
+    }
+}
+
+/// The [workspace] section of a project file, which may also be the only section.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WorkspaceConfig {
+    /// The directories of every member project, relative to the workspace.
+    pub members: Vec<String>,
+}
+
+/// Used to find out if a project file has a [package] and a [workspace], before reading either.
+#[derive(Deserialize)]
+struct ProjectFile {
+    package: Option<::toml::Value>,
+    workspace: Option<WorkspaceConfig>,
+    profile: Option<BTreeMap<String, ProfileConfig>>,
+}
+
+/// A [toolchain] table, like `cc = "gcc-12"`, in a project file or the user's Config.toml.
+/// Any GCC or Clang compatible program may be given, including wrappers like `ccache gcc`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ToolchainConfig {
+    pub cc: Option<String>,
+    pub cxx: Option<String>,
+    /// The archiver, like `llvm-ar`.
+    pub ar: Option<String>,
+    /// The linker, like `lld` or `mold`. GCC and Clang still link with `cc` or `cxx`, which
+    /// are told to use it with `-fuse-ld=`.
+    pub ld: Option<String>,
+}
+
+/// A [target.<triple>] table, used when building with `--target <triple>`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TargetConfig {
+    /// Put in front of `gcc`, `g++` and `ar` when building with GCC. Defaults to the
+    /// triple followed by a dash, as in `aarch64-linux-gnu-gcc`.
+    pub prefix: Option<String>,
+    /// The directory the target's headers and libraries are in, relative to the project.
+    pub sysroot: Option<String>,
+    /// Programs used instead of the ones in [toolchain] for this target.
+    pub cc: Option<String>,
+    pub cxx: Option<String>,
+    pub ar: Option<String>,
+    pub ld: Option<String>,
+    /// Added to the ones in [dependencies] for this target.
+    pub header_search_directories: Option<Vec<String>>,
+    pub linker_search_directories: Option<Vec<String>>,
+    pub link_libraries: Option<Vec<String>>,
+}
+
+/// A [profile.<name>] table. Anything left out is taken from the profile it inherits.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileConfig {
+    /// The profile this one starts out as. Defaults to `debug` for custom profiles.
+    pub inherits: Option<String>,
+    pub opt_level: Option<OptLevel>,
+    /// Whether to generate debug information.
+    pub debug: Option<bool>,
+    /// Preprocessor defines, like `["MAID_DEBUG", "LOG_LEVEL=2"]`. These replace the inherited ones.
+    pub defines: Option<Vec<String>>,
+    /// Whether to use link-time optimization.
+    pub lto: Option<bool>,
+    /// Extra flags for the compiler, used both when compiling and linking.
+    pub flags: Option<Vec<String>>,
+}
+
+/// An optimization level, written either as `opt_level = 2` or `opt_level = "s"`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum OptLevel {
+    Number(u32),
+    Name(String),
+}
+
+/// How many warnings to turn on, written like `warnings = "all"`.
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Warnings {
+    /// Only the warnings the compiler reports without any flags.
+    Default,
+    /// The common warnings (`-Wall`).
+    All,
+    /// Even more warnings (`-Wall -Wextra`).
+    Extra,
+    /// No warnings at all (`-w`).
+    None,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
@@ -49,8 +295,9 @@ fn is_valid_project_name(name: &str) -> bool {
 }
 
 impl Project {
-    /// Creates a new project and returns its properties.
-    pub fn new(name: &str) -> Result<Self, ProjectError> {
+    /// Creates a new project and returns its properties. When `lib` is true, the project is
+    /// a static library with a public header in `include/` instead of an executable.
+    pub fn new(name: &str, lib: bool) -> Result<Self, ProjectError> {
         if !is_valid_project_name(name) {
             return Err(ProjectError { error_type: ProjectErrorType::ProjectNameContainsInvalidCharacters, description: String::from("Project name must match the regex: (a-zA-Z)+") });
         }
@@ -66,11 +313,53 @@ impl Project {
         dir_builder.create(format!("./{}/source", name)).unwrap();
         dir_builder.create(format!("./{}/include", name)).unwrap();
 
-        // Create the template main.c source file
-        let mut source_file = File::create(format!("./{}/source/main.c", name)).unwrap();
-        source_file
-            .write_all(
-                r#"#include <stdio.h>
+        if lib {
+            // Project names may contain '-', which C identifiers cannot
+            let identifier = name.replace("-", "_");
+
+            // Create the template public header
+            let mut header_file = File::create(format!("./{}/include/{}.h", name, name)).unwrap();
+            header_file
+                .write_all(
+                    format!(
+                        r#"#ifndef {guard}_H
+#define {guard}_H
+
+int {identifier}_add(int a, int b);
+
+#endif
+"#,
+                        guard = identifier.to_uppercase(),
+                        identifier = identifier
+                    ).as_bytes(),
+                )
+                .unwrap();
+            header_file.sync_data().unwrap();
+
+            // Create the template source file implementing the header
+            let mut source_file = File::create(format!("./{}/source/{}.c", name, name)).unwrap();
+            source_file
+                .write_all(
+                    format!(
+                        r#"#include "{name}.h"
+
+int {identifier}_add(int a, int b)
+{{
+    return a + b;
+}}
+"#,
+                        name = name,
+                        identifier = identifier
+                    ).as_bytes(),
+                )
+                .unwrap();
+            source_file.sync_data().unwrap();
+        } else {
+            // Create the template main.c source file
+            let mut source_file = File::create(format!("./{}/source/main.c", name)).unwrap();
+            source_file
+                .write_all(
+                    r#"#include <stdio.h>
 
 int main(int argc, char *argv[])
 {
@@ -78,9 +367,10 @@ int main(int argc, char *argv[])
     return 0;
 }
 "#.as_bytes(),
-            )
-            .unwrap();
-        source_file.sync_data().unwrap();
+                )
+                .unwrap();
+            source_file.sync_data().unwrap();
+        }
 
         // Create the project file in the new folder
         let mut project_file = File::create(format!("./{}/Maid.toml", name)).unwrap();
@@ -91,18 +381,34 @@ int main(int argc, char *argv[])
                 name: name.to_owned(),
                 version: String::from("0.1.0"),
                 authors: vec![String::from("Johnny Appleseed")],
-                target: Target::Executable,
+                target: if lib { Target::Static } else { Target::Executable },
+                standard: None,
             },
             build: Some(Build {
                 preferred_compiler: None,
+                warnings: Some(Warnings::Default),
+                warnings_as_errors: None,
+                assembler: None,
+                assembler_format: None,
+                assembler_options: None,
                 gnu_options: Some(vec![]),
                 clang_options: Some(vec![]),
+                msvc_options: Some(vec![]),
             }),
             dependencies: Some(Dependencies {
-                header_search_directories: Some(vec![]),
+                // The library's own sources include its public header
+                header_search_directories: Some(if lib { vec![String::from("./include")] } else { vec![] }),
                 linker_search_directories: Some(vec![]),
                 link_libraries: Some(vec![]),
+                projects: BTreeMap::new(),
             }),
+            workspace: None,
+            profile: None,
+            toolchain: None,
+            target: None,
+            bin: None,
+            run: None,
+            root: PathBuf::from(format!("./{}", name)),
         };
 
         // Serialize the project into TOML
@@ -115,35 +421,144 @@ int main(int argc, char *argv[])
         Ok(project)
     }
 
-    /// Gets the Project in the given directory
-    pub fn get() -> Result<Self, ProjectError> {
+    /// The file name of the binary this project builds for `os`, like `hello`, `libhello.a`, or `libhello.so`.
+    pub fn output_name(&self, os: Os) -> String {
+        let name = &self.package.name;
+        match self.package.target {
+            Target::Executable => executable_name(name, os),
+            Target::Static => format!("lib{}.a", name),
+            Target::Dynamic => match os {
+                Os::Windows => format!("{}.dll", name),
+                Os::MacOS => format!("lib{}.dylib", name),
+                Os::Unix => format!("lib{}.so", name),
+            },
+        }
+    }
+
+    /// The name a dynamic library is looked up by when a program using it runs, which only
+    /// changes with the major version, like `libz.so.1` or `libz.1.dylib`. Windows finds
+    /// DLLs by their file name, so they have none.
+    pub fn soname(&self, os: Os) -> Option<String> {
+        let name = &self.package.name;
+        let version = &self.package.version;
+        let major = version.split('.').next().unwrap_or(version);
+        match (&self.package.target, os) {
+            (&Target::Dynamic, Os::MacOS) => Some(format!("lib{}.{}.dylib", name, major)),
+            (&Target::Dynamic, Os::Unix) => Some(format!("lib{}.so.{}", name, major)),
+            _ => None,
+        }
+    }
+
+    /// Every executable this project builds: the one named after the package from
+    /// `source/main.c` (for Executable projects), one for every source in `source/bin/`,
+    /// and every [[bin]]. A [[bin]] replaces the source in `source/bin/` of the same name.
+    /// Any other two executables with the same name would overwrite each other in target/,
+    /// so those are an error.
+    pub fn binaries(&self) -> Result<Vec<Binary>, ProjectError> {
+        let mut binaries = Vec::<Binary>::new();
+        let duplicate = |name: &str, source: &Path| ProjectError {
+            error_type: ProjectErrorType::DuplicateBinary,
+            description: format!(
+                "`{}` has more than one binary named `{}`, rename `{}`.",
+                self.package.name,
+                name,
+                source.display()
+            ),
+        };
+
+        if let Some(ref bins) = self.bin {
+            for bin in bins {
+                if binaries.iter().any(|binary| binary.name == bin.name) {
+                    return Err(duplicate(&bin.name, Path::new(&bin.path)));
+                }
+                binaries.push(Binary {
+                    name: bin.name.clone(),
+                    source: self.path(&bin.path),
+                });
+            }
+        }
+
+        // Every source directly inside of source/bin/, named after its file
+        let bin_directory = self.root.join("source").join("bin");
+        if let Ok(entries) = ::std::fs::read_dir(&bin_directory) {
+            let mut sources: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && Language::of(path).is_some())
+                .collect();
+            sources.sort();
+
+            for source in sources {
+                let name = source.file_stem().unwrap().to_string_lossy().into_owned();
+                if !binaries.iter().any(|binary| binary.name == name) {
+                    binaries.push(Binary {
+                        name: name,
+                        source: source,
+                    });
+                }
+            }
+        }
+
+        if self.package.target == Target::Executable {
+            let main = ::std::fs::read_dir(self.root.join("source"))
+                .into_iter()
+                .flat_map(|entries| entries.filter_map(|entry| entry.ok().map(|entry| entry.path())))
+                .find(|path| {
+                    path.is_file() && path.file_stem().and_then(|stem| stem.to_str()) == Some("main")
+                        && Language::of(path).is_some()
+                });
+            if let Some(main) = main {
+                // Like a [[bin]] or a source/bin/ named after the package
+                if let Some(binary) = binaries.iter().find(|binary| binary.name == self.package.name) {
+                    return Err(duplicate(&binary.name, &binary.source));
+                }
+                binaries.insert(
+                    0,
+                    Binary {
+                        name: self.package.name.clone(),
+                        source: main,
+                    },
+                );
+            }
+        }
+
+        Ok(binaries)
+    }
+
+    /// The [target.<triple>] table for `triple`, if we are cross compiling and there is one.
+    pub fn target_config(&self, triple: Option<&Triple>) -> Option<&TargetConfig> {
+        match (triple, &self.target) {
+            (Some(triple), &Some(ref targets)) => targets.get(&triple.name),
+            _ => None,
+        }
+    }
+
+    /// Gets the Project whose Maid.toml is in `root`
+    pub fn open(root: &Path) -> Result<Self, ProjectError> {
         // Open the project file
-        let mut project_file = match File::open("./Maid.toml") {
+        let mut project_file = match File::open(root.join("Maid.toml")) {
             Ok(val) => val,
-            Err(_) => match File::open("../Maid.toml") {
-                Ok(val) => val,
-                Err(_) => {
-                    return Err(ProjectError {
-                        error_type: ProjectErrorType::MaidFileNotFound,
-                        description: String::from("No Maid.toml in the current directory."),
-                    })
-                }
-            },
+            Err(_) => {
+                return Err(ProjectError {
+                    error_type: ProjectErrorType::MaidFileNotFound,
+                    description: format!("No Maid.toml in `{}`.", root.display()),
+                })
+            }
         };
 
         let mut contents = String::new();
         project_file.read_to_string(&mut contents).unwrap();
 
         // Deserialize the TOML
-        let project: Project = match ::toml::from_str(&contents) {
+        let mut project: Project = match ::toml::from_str(&contents) {
             Ok(value) => value,
-            Err(_) => {
+            Err(e) => {
                 return Err(ProjectError {
                     error_type: ProjectErrorType::ProjectFileCouldNotBeParsed,
-                    description: String::from("The project file could not be parsed."),
+                    description: format!("The project file could not be parsed: {}", e),
                 })
             }
         };
+        project.root = root.to_owned();
 
         if is_valid_project_name(&project.package.name) {
             Ok(project)
@@ -151,6 +566,261 @@ int main(int argc, char *argv[])
             Err(ProjectError { error_type: ProjectErrorType::ProjectNameContainsInvalidCharacters, description: String::from("Project name must match the regex: (a-zA-Z)+") })
         }
     }
+
+    /// Resolves a path from the project file against the project's root directory.
+    pub fn path(&self, path: &str) -> PathBuf {
+        // Avoid paths like `././include` or `./app/../core`
+        ::utils::normalize_path(&self.root.join(path))
+    }
+}
+
+/// The file name of an executable for `os`, like `hello` or `hello.exe`.
+pub fn executable_name(name: &str, os: Os) -> String {
+    if os == Os::Windows {
+        format!("{}.exe", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Every project built together from one root project file, sharing one `target/` directory.
+/// A project file without a [workspace] is a workspace with a single member.
+pub struct Workspace {
+    pub root: PathBuf,
+    /// Ordered so that every member comes after the members it depends on.
+    pub members: Vec<Project>,
+    /// The profiles of the root project file, which every member is built with.
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+impl Workspace {
+    /// Finds the workspace to work on. With a `manifest_path`, that project file is used.
+    /// Otherwise, the closest Maid.toml in the current directory or any directory above it is
+    /// used, so maid can be run from anywhere within a project.
+    ///
+    /// When that project is a member of a workspace further up, only that member is worked on,
+    /// but it is still built into the workspace's `target/` directory.
+    ///
+    /// This changes the current directory to the root of the workspace, so that every path we
+    /// hand to the compiler is the same no matter where maid was run from.
+    pub fn get(manifest_path: Option<&Path>) -> Result<Self, ProjectError> {
+        let not_found = |description: String| ProjectError {
+            error_type: ProjectErrorType::MaidFileNotFound,
+            description: description,
+        };
+
+        let closest = match manifest_path {
+            // Accept both `path/to/Maid.toml` and `path/to`
+            Some(manifest_path) => {
+                let root = if manifest_path.is_dir() {
+                    manifest_path
+                } else {
+                    manifest_path.parent().unwrap_or(Path::new("."))
+                };
+                let root = if root.as_os_str().is_empty() { Path::new(".") } else { root };
+                match root.canonicalize() {
+                    Ok(root) => root,
+                    Err(_) => return Err(not_found(format!("`{}` does not exist.", root.display()))),
+                }
+            }
+            None => {
+                let current = env::current_dir().unwrap();
+                match current.ancestors().find(|d| d.join("Maid.toml").is_file()) {
+                    Some(directory) => directory.to_owned(),
+                    None => {
+                        return Err(not_found(String::from(
+                            "No Maid.toml in the current directory or any directory above it.",
+                        )))
+                    }
+                }
+            }
+        };
+
+        // See if a workspace further up has this project as one of its members
+        for directory in closest.ancestors().skip(1) {
+            if !directory.join("Maid.toml").is_file() {
+                continue;
+            }
+
+            if let Ok(outer) = Workspace::open(directory) {
+                let member = outer
+                    .members
+                    .into_iter()
+                    .find(|member| member.root.canonicalize().ok().as_ref() == Some(&closest));
+                if let Some(member) = member {
+                    let relative = member.root.strip_prefix(directory).unwrap().to_owned();
+                    env::set_current_dir(directory).unwrap();
+                    return Ok(Workspace {
+                        root: PathBuf::from("."),
+                        members: vec![Project::open(&Path::new(".").join(relative))?],
+                        profiles: outer.profiles,
+                    });
+                }
+            }
+        }
+
+        env::set_current_dir(&closest).unwrap();
+        Workspace::open(Path::new("."))
+    }
+
+    /// Gets the workspace whose Maid.toml is in `root`
+    pub fn open(root: &Path) -> Result<Self, ProjectError> {
+        let mut contents = String::new();
+        match File::open(root.join("Maid.toml")) {
+            Ok(mut file) => file.read_to_string(&mut contents).unwrap(),
+            Err(_) => {
+                return Err(ProjectError {
+                    error_type: ProjectErrorType::MaidFileNotFound,
+                    description: format!("No Maid.toml in `{}`.", root.display()),
+                })
+            }
+        };
+
+        let file: ProjectFile = match ::toml::from_str(&contents) {
+            Ok(value) => value,
+            Err(e) => {
+                return Err(ProjectError {
+                    error_type: ProjectErrorType::ProjectFileCouldNotBeParsed,
+                    description: format!("The project file could not be parsed: {}", e),
+                })
+            }
+        };
+
+        let mut members = Vec::<Project>::new();
+
+        // The root may be a project of its own, as well as the workspace
+        if file.package.is_some() {
+            members.push(Project::open(root)?);
+        }
+
+        match file.workspace {
+            Some(workspace) => for member in workspace.members {
+                members.push(Project::open(&::utils::normalize_path(&root.join(member)))?);
+            },
+            None => if members.is_empty() {
+                return Err(ProjectError {
+                    error_type: ProjectErrorType::ProjectFileCouldNotBeParsed,
+                    description: String::from("The project file has neither a [package] nor a [workspace]."),
+                });
+            },
+        }
+
+        Ok(Workspace {
+            root: root.to_owned(),
+            members: order_by_dependencies(members),
+            profiles: file.profile.unwrap_or_default(),
+        })
+    }
+
+    /// The directory every member is built into.
+    pub fn target_directory(&self) -> PathBuf {
+        self.root.join("target")
+    }
+
+    /// The project `maid run` runs: the root project, or else the only Executable member.
+    pub fn executable(&self) -> Option<&Project> {
+        if let Some(root) = self.members.iter().find(|m| m.root == self.root) {
+            return Some(root);
+        }
+
+        let mut executables = self.members
+            .iter()
+            .filter(|m| m.package.target == Target::Executable);
+        match (executables.next(), executables.next()) {
+            (Some(project), None) => Some(project),
+            _ => None,
+        }
+    }
+
+    /// The executable to run: the one named `name` in any member, or else the only one of
+    /// the project `executable()` finds, or the one named after its package.
+    pub fn binary(&self, name: Option<&str>) -> Result<(&Project, Binary), ProjectError> {
+        if let Some(name) = name {
+            for member in &self.members {
+                if let Some(binary) = member.binaries()?.into_iter().find(|b| b.name == name) {
+                    return Ok((member, binary));
+                }
+            }
+            return Err(ProjectError {
+                error_type: ProjectErrorType::BinaryNotFound,
+                description: format!("There is no binary named `{}` in this workspace.", name),
+            });
+        }
+
+        let project = match self.executable() {
+            Some(project) => project,
+            None => {
+                return Err(ProjectError {
+                    error_type: ProjectErrorType::BinaryNotFound,
+                    description: String::from(
+                        "There is no single Executable project to run in this workspace.",
+                    ),
+                })
+            }
+        };
+
+        let binaries = project.binaries()?;
+        let names: Vec<String> = binaries.iter().map(|binary| binary.name.clone()).collect();
+        let mut binaries = binaries.into_iter();
+        match (binaries.next(), binaries.next()) {
+            (Some(binary), None) => Ok((project, binary)),
+            (None, _) => Err(ProjectError {
+                error_type: ProjectErrorType::BinaryNotFound,
+                description: format!("`{}` has no binaries to run.", project.package.name),
+            }),
+            // The package's own executable always comes first
+            (Some(binary), Some(_)) => if binary.name == project.package.name {
+                Ok((project, binary))
+            } else {
+                Err(ProjectError {
+                    error_type: ProjectErrorType::BinaryNotFound,
+                    description: format!(
+                        "`{}` has more than one binary, choose one with `--bin`: {}",
+                        project.package.name,
+                        names.join(", ")
+                    ),
+                })
+            },
+        }
+    }
+}
+
+/// Sorts projects so that every project comes after the projects it depends on by `path`.
+fn order_by_dependencies(projects: Vec<Project>) -> Vec<Project> {
+    fn canonical(path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_owned())
+    }
+
+    fn visit(index: usize, projects: &[Project], roots: &[PathBuf], visited: &mut Vec<bool>, ordered: &mut Vec<usize>) {
+        if visited[index] {
+            return;
+        }
+        visited[index] = true;
+
+        // Visit every member this project depends on before adding it
+        if let Some(ref dependencies) = projects[index].dependencies {
+            for dependency in dependencies.projects.values() {
+                if let Some(ref path) = dependency.path {
+                    let root = canonical(&projects[index].path(path));
+                    if let Some(other) = roots.iter().position(|r| *r == root) {
+                        visit(other, projects, roots, visited, ordered);
+                    }
+                }
+            }
+        }
+
+        ordered.push(index);
+    }
+
+    let roots: Vec<PathBuf> = projects.iter().map(|p| canonical(&p.root)).collect();
+    let mut visited = vec![false; projects.len()];
+    let mut ordered = Vec::<usize>::new();
+    for index in 0..projects.len() {
+        visit(index, &projects, &roots, &mut visited, &mut ordered);
+    }
+
+    let mut projects: Vec<Option<Project>> = projects.into_iter().map(Some).collect();
+    ordered.into_iter().map(|i| projects[i].take().unwrap()).collect()
 }
 
 #[derive(Debug)]
@@ -159,6 +829,8 @@ pub enum ProjectErrorType {
     ProjectFileCouldNotBeParsed,
     ProjectNameContainsInvalidCharacters,
     ProjectWithSameNameAlreadyExists,
+    BinaryNotFound,
+    DuplicateBinary,
 }
 
 #[derive(Debug)]
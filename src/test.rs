@@ -0,0 +1,216 @@
+//! Runs the programs built from `tests/`, each as its own process, and every `MAID_TEST`
+//! case in a process of its own too, so one crashing case cannot take the others down.
+//!
+//! A test passes when its process exits successfully. Everything it prints is captured,
+//! and only shown when it fails, so the output of passing tests stays out of the way.
+
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use build::Program;
+use utils;
+use ansi_term::Color::{Green, Red, Yellow};
+
+/// How a program finished.
+pub enum Outcome {
+    Exited(ExitStatus),
+    /// It ran for longer than it was allowed to, and was killed.
+    TimedOut,
+}
+
+/// The name of the variable the dynamic loader searches for libraries with.
+pub fn library_path_variable() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "PATH"
+    } else if cfg!(target_os = "macos") {
+        "DYLD_LIBRARY_PATH"
+    } else {
+        "LD_LIBRARY_PATH"
+    }
+}
+
+/// Puts `directories` in front of those the dynamic loader searches, so a program finds
+/// the dynamic libraries it was linked against without them being installed.
+pub fn library_path(directories: &[PathBuf]) -> OsString {
+    let existing = env::var_os(library_path_variable()).unwrap_or_default();
+    let mut paths = directories.to_vec();
+    paths.extend(env::split_paths(&existing));
+    // A directory with the separator in its name cannot be searched, so nothing changes
+    env::join_paths(paths).unwrap_or(existing)
+}
+
+/// Reads everything from a pipe on its own thread, so a program filling one pipe never
+/// blocks while we wait on the other.
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        output
+    })
+}
+
+/// Runs a program from its project's root, killing it once `timeout` has passed, if there
+/// is one. Returns how it finished, everything it printed, and how long it took.
+pub fn run_program(
+    program: &Program,
+    arguments: &[String],
+    timeout: Option<Duration>,
+) -> io::Result<(Outcome, Vec<u8>, Duration)> {
+    // The paths are relative to where maid runs, but the program starts in its project's root
+    let path = utils::absolute_path(&program.path);
+    let library_directories: Vec<PathBuf> = program
+        .library_directories
+        .iter()
+        .map(|directory| utils::absolute_path(directory))
+        .collect();
+
+    let start = Instant::now();
+    let mut child = Command::new(&path)
+        .args(arguments)
+        .current_dir(&program.root)
+        .env(library_path_variable(), library_path(&library_directories))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let outcome = match timeout {
+        // Waiting on the process directly is what keeps the timing precise
+        None => Outcome::Exited(child.wait()?),
+        Some(timeout) => loop {
+            if let Some(status) = child.try_wait()? {
+                break Outcome::Exited(status);
+            }
+            if start.elapsed() >= timeout {
+                // It may have exited in the meantime, which is fine
+                let _ = child.kill();
+                child.wait()?;
+                break Outcome::TimedOut;
+            }
+            thread::sleep(Duration::from_millis(10));
+        },
+    };
+    let elapsed = start.elapsed();
+
+    let mut output = stdout.join().unwrap_or_default();
+    output.extend(stderr.join().unwrap_or_default());
+    Ok((outcome, output, elapsed))
+}
+
+/// A single test: a whole program, or one `MAID_TEST` case of the generated one.
+#[derive(Clone)]
+struct Test {
+    /// The name it is shown and filtered by, like `my-lib/parser`.
+    name: String,
+    program: Program,
+    arguments: Vec<String>,
+}
+
+/// Lists every test of the programs, running each `MAID_TEST` case on its own.
+fn list_tests(programs: &[Program]) -> Vec<Test> {
+    let mut tests = Vec::<Test>::new();
+    for program in programs {
+        if program.cases.is_empty() {
+            tests.push(Test {
+                name: format!("{}/{}", program.package, program.name),
+                program: program.clone(),
+                arguments: vec![],
+            });
+        }
+        for case in &program.cases {
+            tests.push(Test {
+                name: format!("{}/{}", program.package, case),
+                program: program.clone(),
+                arguments: vec![case.clone()],
+            });
+        }
+    }
+    tests
+}
+
+/// Runs every test whose name contains `filter` on at most `workers` threads, printing
+/// the results as they come in. Returns false if any of them failed.
+pub fn run(programs: &[Program], filter: Option<&str>, workers: usize, timeout: Duration) -> bool {
+    let tests = list_tests(programs);
+    let selected: Vec<Test> = tests
+        .iter()
+        .filter(|test| match filter {
+            Some(filter) => test.name.contains(filter),
+            None => true,
+        })
+        .cloned()
+        .collect();
+    let filtered = tests.len() - selected.len();
+
+    println!(
+        "\nrunning {} test{}",
+        selected.len(),
+        if selected.len() == 1 { "" } else { "s" }
+    );
+
+    // The passed, failed, and timed out counts, also held while printing a result
+    let results = Arc::new(Mutex::new((0, 0, 0)));
+
+    let counts = results.clone();
+    utils::parallel(selected, workers, move |test| {
+        let result = run_program(&test.program, &test.arguments, Some(timeout));
+
+        let mut results = counts.lock().unwrap();
+        let (status, output) = match result {
+            Ok((Outcome::Exited(ref status), ref output, _)) if status.success() => {
+                results.0 += 1;
+                (Green.paint("ok"), None)
+            }
+            Ok((Outcome::Exited(status), output, _)) => {
+                results.1 += 1;
+                let mut output = output;
+                output.extend(format!("{}\n", status).into_bytes());
+                (Red.paint("FAILED"), Some(output))
+            }
+            Ok((Outcome::TimedOut, output, _)) => {
+                results.2 += 1;
+                let mut output = output;
+                output.extend(format!("killed after {} seconds\n", timeout.as_secs()).into_bytes());
+                (Yellow.paint("TIMEOUT"), Some(output))
+            }
+            Err(e) => {
+                results.1 += 1;
+                let message =
+                    format!("Failed to execute `{}`: {}\n", test.program.path.display(), e);
+                (Red.paint("FAILED"), Some(message.into_bytes()))
+            }
+        };
+
+        println!("test {} ... {}", test.name, status);
+        if let Some(output) = output {
+            println!("---- {} output ----", test.name);
+            io::stdout().write_all(&output).unwrap();
+        }
+
+        // Every test runs, whether or not the others passed
+        true
+    });
+
+    let (passed, failed, timed_out) = *results.lock().unwrap();
+    let success = failed == 0 && timed_out == 0;
+    println!(
+        "\ntest result: {}. {} passed; {} failed; {} timed out; {} filtered out\n",
+        if success { Green.paint("ok") } else { Red.paint("FAILED") },
+        passed,
+        failed,
+        timed_out,
+        filtered
+    );
+
+    success
+}
@@ -3,10 +3,13 @@ use std::fs::File;
 use std::io::{Read, Write};
 use std::env::current_exe;
 use build::{detect_available_compilers, Compiler};
+use project::ToolchainConfig;
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub preferred_compiler: Compiler,
+    /// Programs to build every project with, unless its own project file says otherwise.
+    pub toolchain: Option<ToolchainConfig>,
 }
 
 impl Config {
@@ -84,6 +87,7 @@ impl Config {
             // Initialize the configuration
             let config = Config {
                 preferred_compiler: default_compiler,
+                toolchain: None,
             };
             println!("defaulted compiler: {:?}", default_compiler);
 
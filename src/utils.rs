@@ -1,44 +1,142 @@
-use std::process::{Command, ExitStatus};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::fs;
+use std::env;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
-pub fn string_to_vec(string: &str) -> Vec<String> {
-    string.split_whitespace().map(|s| s.to_owned()).collect()
+/// Makes a path relative to the current directory absolute, so it still points to the
+/// same file for a program started in another directory.
+pub fn absolute_path(path: &Path) -> PathBuf {
+    match env::current_dir() {
+        Ok(current_directory) => normalize_path(&current_directory.join(path)),
+        Err(_) => path.to_path_buf(),
+    }
 }
 
-/// Takes a String and turns all occurrences of '/' into '\'.
-pub fn windows_path(path: &str) -> String {
-    path.chars()
-        .map(|c| if c == '/' { '\\' } else { c })
-        .collect()
+/// Links `link` to `target`, which is relative to the directory of `link`.
+#[cfg(unix)]
+pub fn symlink(target: &Path, link: &Path) -> ::std::io::Result<()> {
+    ::std::os::unix::fs::symlink(target, link)
 }
 
-/// Executes a shell command and waits for it to finish
-pub fn shell_command(command: &str, silent: bool) -> Result<ExitStatus, ::std::io::Error> {
-    let command = if cfg!(windows) {
-        string_to_vec(&windows_path(command))
+// Only Unix libraries are linked by their soname, but copying keeps this building elsewhere
+#[cfg(not(unix))]
+pub fn symlink(target: &Path, link: &Path) -> ::std::io::Result<()> {
+    fs::copy(link.parent().unwrap().join(target), link).map(|_| ())
+}
+
+/// Removes `.` and `name/..` from a path without touching the file system, so that
+/// `./app/../core` and `./core` are written the same way.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` at the start, or after the root, has to stay
+                _ => normalized.push(".."),
+            },
+            _ => normalized.push(component.as_os_str()),
+        }
+    }
+
+    // Keep relative paths looking relative, like `./core`
+    if path.starts_with(".") && !normalized.starts_with("..") {
+        Path::new(".").join(normalized)
+    } else if normalized.as_os_str().is_empty() {
+        PathBuf::from(".")
     } else {
-        string_to_vec(&command)
-    };
+        normalized
+    }
+}
+
+/// The number of logical CPUs on this machine, or 1 if it cannot be determined.
+pub fn cpu_count() -> usize {
+    match ::std::thread::available_parallelism() {
+        Ok(count) => count.get(),
+        Err(_) => 1,
+    }
+}
+
+/// Calls `work` with every item on at most `workers` threads, returning false if any of
+/// the calls did. Once one has, no new items are started, but those already running finish.
+pub fn parallel<T, F>(items: Vec<T>, workers: usize, work: F) -> bool
+where
+    T: Send + 'static,
+    F: Fn(T) -> bool + Send + Sync + 'static,
+{
+    let queue = Arc::new(Mutex::new(items.into_iter()));
+    let work = Arc::new(work);
+    let failed = Arc::new(AtomicBool::new(false));
+
+    let handles: Vec<_> = (0..workers.max(1))
+        .map(|_| {
+            let queue = queue.clone();
+            let work = work.clone();
+            let failed = failed.clone();
+
+            thread::spawn(move || loop {
+                if failed.load(Ordering::SeqCst) {
+                    break;
+                }
 
-    let result = if silent {
-        Command::new(&command[0])
-            .args(&command[1..])
-            .output()?
-            .status
+                // Take the next item, releasing the queue before working on it
+                let item = match queue.lock().unwrap().next() {
+                    Some(item) => item,
+                    None => break,
+                };
+
+                if !work(item) {
+                    failed.store(true, Ordering::SeqCst);
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    !failed.load(Ordering::SeqCst)
+}
+
+/// The directory maid keeps downloaded dependencies in: `$MAID_HOME`, or `~/.maid`.
+pub fn maid_home() -> PathBuf {
+    if let Some(home) = env::var_os("MAID_HOME") {
+        return PathBuf::from(home);
+    }
+
+    let home = if cfg!(target_os = "windows") {
+        env::var_os("USERPROFILE")
     } else {
-        if cfg!(target_os = "windows") {
-            Command::new("cmd").arg("/C").args(command).spawn()?.wait()?
-        } else {
-            Command::new("sh").arg("-c").args(command).spawn()?.wait()?
-        }
+        env::var_os("HOME")
     };
-
-    Ok(result)
+    match home {
+        Some(home) => PathBuf::from(home).join(".maid"),
+        // Without a home directory, fall back to the working directory
+        None => PathBuf::from(".maid"),
+    }
 }
 
-pub fn shell_command_exists(command: &str) -> bool {
-    shell_command(command, true).unwrap().success()
+/// Writes `contents` to `path`, unless the file already has them. Anything watching the
+/// file, like an editor or the modification times we compare, would otherwise see a change.
+pub fn write_if_changed(path: &Path, contents: &str) -> ::std::io::Result<()> {
+    let mut previous = String::new();
+    if let Ok(mut file) = fs::File::open(path) {
+        if file.read_to_string(&mut previous).is_err() {
+            previous.clear();
+        }
+    }
+    if previous == contents {
+        return Ok(());
+    }
+
+    fs::File::create(path)?.write_all(contents.as_bytes())
 }
 
 pub fn get_files_in_directory(directory: &Path, recursive: bool) -> Vec<PathBuf> {